@@ -3,8 +3,6 @@ use glm::{vec2, vec3, Mat4, Vec2, Vec3};
 use nalgebra_glm as glm;
 use crate::config::HEIGHT_OFFSET;
 
-use std::time::Duration;
-
 #[derive(Debug)]
 pub struct CameraState {
     eye: Vec3,
@@ -15,9 +13,11 @@ pub struct CameraState {
 }
 
 const CAMERA_DISTANCE: f32 = 10.0;
-const ZOOM_SPEED: f32 = 0.05;
-const PAN_SPEED: f32 = 0.05;
-const ROTATION_SPEED: f32 = 1000.;
+// units per second
+const ZOOM_SPEED: f32 = 3.0;
+const PAN_SPEED: f32 = 3.0;
+// radians per unit of mouse motion
+const ROTATION_SPEED: f32 = 0.002;
 
 impl CameraState {
     pub fn new() -> CameraState {
@@ -56,37 +56,49 @@ impl CameraState {
         &self.look
     }
 
-    pub fn update(&mut self) {
+    pub fn set_position(&mut self, eye: Vec3) {
+        self.eye = eye;
+    }
+
+    pub fn is_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    // free-flight velocity from the held keys, in units per second
+    pub fn fly_velocity(&self) -> Vec3 {
         let tangent = glm::cross(&self.look, &self.up);
+        let mut velocity = vec3(0., 0., 0.);
 
         if self.keys.contains(&VirtualKeyCode::W) {
-            self.eye += ZOOM_SPEED * self.look;
+            velocity += ZOOM_SPEED * self.look;
         }
 
         if self.keys.contains(&VirtualKeyCode::Space) {
-            self.eye += PAN_SPEED * self.up;
+            velocity += PAN_SPEED * self.up;
         }
 
         if self.keys.contains(&VirtualKeyCode::A) {
-            self.eye += PAN_SPEED * tangent;
+            velocity += PAN_SPEED * tangent;
         }
 
         if self.keys.contains(&VirtualKeyCode::S) {
-            self.eye -= ZOOM_SPEED * self.look;
+            velocity -= ZOOM_SPEED * self.look;
         }
 
         if self.keys.contains(&VirtualKeyCode::LShift)
             || self.keys.contains(&VirtualKeyCode::RShift)
         {
-            self.eye -= PAN_SPEED * self.up;
+            velocity -= PAN_SPEED * self.up;
         }
 
         if self.keys.contains(&VirtualKeyCode::D) {
-            self.eye -= PAN_SPEED * tangent;
+            velocity -= PAN_SPEED * tangent;
         }
+
+        velocity
     }
 
-    pub fn process_input(&mut self, pressed: bool, key: VirtualKeyCode, _dt: Duration) {
+    pub fn process_input(&mut self, pressed: bool, key: VirtualKeyCode) {
         println!(
             "{} key: {:#?}!",
             if pressed { "Pressed" } else { "Released" },
@@ -100,9 +112,9 @@ impl CameraState {
         }
     }
 
-    pub fn process_cursor(&mut self, delta: (f64, f64), dt: Duration) {
-        // println!("Process {} {} {} {}", dt.as_millis(), dt.as_secs_f32(), dt.as_nanos(), dt.as_micros());
-        let angle = ROTATION_SPEED * dt.as_secs_f32();
+    pub fn process_cursor(&mut self, delta: (f64, f64)) {
+        // mouse deltas are already a distance, so rotation doesn't scale with time
+        let angle = ROTATION_SPEED;
         let delta = vec2(delta.0 as f32, delta.1 as f32);
         self.move_velocity = glm::lerp(&delta, &self.move_velocity, 0.5);
         // println!("Angle {}  Move {}", angle, self.move_velocity);
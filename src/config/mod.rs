@@ -1,2 +1,3 @@
 pub const RENDER_DISTANCE: i32 = 4;
 pub const HEIGHT_OFFSET: i32 = 16;
+pub const TICKS_PER_SECOND: u32 = 60;
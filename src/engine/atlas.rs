@@ -0,0 +1,204 @@
+use crate::engine::block::BlockType;
+use glium::texture::{RawImage2d, Texture2d};
+use glium::Display;
+use image::{imageops, Rgba, RgbaImage};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// textures are scaled to this many pixels square as they're packed
+pub const TILE_SIZE: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Face {
+    Top,
+    Side,
+    Bottom,
+}
+
+impl Face {
+    pub const ALL: [Face; 3] = [Face::Top, Face::Side, Face::Bottom];
+
+    pub fn of_normal(normal: &[i32; 3]) -> Face {
+        match normal[1] {
+            1 => Face::Top,
+            -1 => Face::Bottom,
+            _ => Face::Side,
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match *self {
+            Face::Top => "top",
+            Face::Side => "side",
+            Face::Bottom => "bottom",
+        }
+    }
+}
+
+// corners of a tile in texture coordinates, v runs up the texture as in gl
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+// Every block texture in a pack laid out on one grid, so the whole world can be drawn with a
+// single texture bound. The first tile is plain white, which faces without a texture of their
+// own use to keep their block's colour.
+pub struct Atlas {
+    image: RgbaImage,
+    columns: u32,
+    tiles: HashMap<(BlockType, Face), u32>,
+}
+
+impl Atlas {
+    // no textures at all, every block keeps its flat colour
+    pub fn blank() -> Atlas {
+        Atlas::pack(Vec::new())
+    }
+
+    // Reads a texture pack directory. Each block looks for "<id>_top.png", "<id>_side.png" and
+    // "<id>_bottom.png", falling back to "<id>.png" for any face without its own file, and
+    // keeps its colour if there's neither.
+    pub fn load(dir: &Path) -> image::ImageResult<Atlas> {
+        let mut images: HashMap<PathBuf, RgbaImage> = HashMap::new();
+        let mut textures = Vec::new();
+        for block_type in BlockType::ALL.iter() {
+            for face in Face::ALL.iter() {
+                let candidates = [format!("{}_{}.png", block_type.id(), face.suffix()), format!("{}.png", block_type.id())];
+                let path = match candidates.iter().map(|name| dir.join(name)).find(|path| path.is_file()) {
+                    Some(path) => path,
+                    None => continue,
+                };
+                if !images.contains_key(&path) {
+                    images.insert(path.clone(), image::open(&path)?.to_rgba());
+                }
+                textures.push(((*block_type, *face), images[&path].clone()));
+            }
+        }
+        println!("Loaded {} textures from {}", images.len(), dir.display());
+        Ok(Atlas::pack(textures))
+    }
+
+    // Lays the textures out left to right, top to bottom on a square-ish grid after the blank
+    // tile. Faces sharing an identical image share a tile.
+    pub fn pack(textures: Vec<((BlockType, Face), RgbaImage)>) -> Atlas {
+        let mut unique: Vec<RgbaImage> = vec![RgbaImage::from_pixel(TILE_SIZE, TILE_SIZE, Rgba([255; 4]))];
+        let mut tiles = HashMap::new();
+        for (key, texture) in textures {
+            let texture = if texture.dimensions() == (TILE_SIZE, TILE_SIZE) {
+                texture
+            } else {
+                imageops::resize(&texture, TILE_SIZE, TILE_SIZE, imageops::FilterType::Nearest)
+            };
+            let index = match unique.iter().position(|t| *t == texture) {
+                Some(index) => index,
+                None => {
+                    unique.push(texture);
+                    unique.len() - 1
+                }
+            };
+            tiles.insert(key, index as u32);
+        }
+
+        let columns = (unique.len() as f32).sqrt().ceil() as u32;
+        let rows = (unique.len() as f32 / columns as f32).ceil() as u32;
+        let mut image = RgbaImage::new(columns * TILE_SIZE, rows * TILE_SIZE);
+        for (i, texture) in unique.iter().enumerate() {
+            let i = i as u32;
+            imageops::replace(&mut image, texture, i % columns * TILE_SIZE, i / columns * TILE_SIZE);
+        }
+        Atlas { image, columns, tiles }
+    }
+
+    pub fn tile(&self, block_type: BlockType, face: Face) -> Option<Tile> {
+        self.tiles.get(&(block_type, face)).map(|index| self.tile_at(*index))
+    }
+
+    pub fn blank_tile(&self) -> Tile {
+        self.tile_at(0)
+    }
+
+    // inset half a pixel so filtering never reaches into the next tile
+    fn tile_at(&self, index: u32) -> Tile {
+        let (width, height) = (self.image.width() as f32, self.image.height() as f32);
+        let (x, y) = ((index % self.columns * TILE_SIZE) as f32, (index / self.columns * TILE_SIZE) as f32);
+        let size = TILE_SIZE as f32;
+        // the image's rows run down, the texture's v runs up
+        Tile {
+            min: [(x + 0.5) / width, 1. - (y + size - 0.5) / height],
+            max: [(x + size - 0.5) / width, 1. - (y + 0.5) / height],
+        }
+    }
+
+    pub fn texture(&self, display: &Display) -> Texture2d {
+        let image = RawImage2d::from_raw_rgba_reversed(&self.image.clone().into_raw(), self.image.dimensions());
+        Texture2d::new(display, image).expect("to create atlas texture")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(r: u8, g: u8, b: u8) -> RgbaImage {
+        RgbaImage::from_pixel(TILE_SIZE, TILE_SIZE, Rgba([r, g, b, 255]))
+    }
+
+    // the pixel of the atlas image under a texture coordinate
+    fn sample(atlas: &Atlas, uv: [f32; 2]) -> Rgba<u8> {
+        let x = (uv[0] * atlas.image.width() as f32) as u32;
+        let y = ((1. - uv[1]) * atlas.image.height() as f32) as u32;
+        *atlas.image.get_pixel(x, y)
+    }
+
+    #[test]
+    fn test_blank_atlas_is_one_white_tile() {
+        let atlas = Atlas::blank();
+        assert_eq!(atlas.image.dimensions(), (TILE_SIZE, TILE_SIZE));
+        assert_eq!(atlas.tile(BlockType::STONE, Face::Side), None);
+        assert_eq!(sample(&atlas, atlas.blank_tile().min), Rgba([255; 4]));
+    }
+
+    #[test]
+    fn test_tiles_land_on_their_textures() {
+        let textures = vec![
+            ((BlockType::GRASS, Face::Top), filled(0, 200, 0)),
+            ((BlockType::GRASS, Face::Side), filled(100, 150, 50)),
+            ((BlockType::GRASS, Face::Bottom), filled(120, 80, 40)),
+            ((BlockType::DIRT, Face::Top), filled(120, 80, 40)),
+            ((BlockType::STONE, Face::Top), filled(128, 128, 128)),
+        ];
+        let atlas = Atlas::pack(textures.clone());
+        // four distinct textures and the blank tile fit a 3 x 2 grid
+        assert_eq!(atlas.image.dimensions(), (3 * TILE_SIZE, 2 * TILE_SIZE));
+        assert_eq!(atlas.tile(BlockType::GRASS, Face::Bottom), atlas.tile(BlockType::DIRT, Face::Top));
+        for ((block_type, face), texture) in textures {
+            let tile = atlas.tile(block_type, face).unwrap();
+            assert_eq!(sample(&atlas, tile.min), *texture.get_pixel(0, 0));
+            assert_eq!(sample(&atlas, tile.max), *texture.get_pixel(0, 0));
+        }
+    }
+
+    #[test]
+    fn test_textures_are_scaled_to_the_tile() {
+        let atlas = Atlas::pack(vec![((BlockType::SAND, Face::Top), RgbaImage::from_pixel(64, 64, Rgba([220, 200, 120, 255])))]);
+        assert_eq!(atlas.image.dimensions(), (2 * TILE_SIZE, TILE_SIZE));
+    }
+
+    #[test]
+    fn test_loads_a_pack_directory() {
+        let dir = std::env::temp_dir().join(format!("boxel-pack-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        filled(0, 200, 0).save(dir.join("grass_top.png")).unwrap();
+        filled(120, 80, 40).save(dir.join("grass.png")).unwrap();
+        let atlas = Atlas::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let top = atlas.tile(BlockType::GRASS, Face::Top).unwrap();
+        assert_eq!(sample(&atlas, top.min), Rgba([0, 200, 0, 255]));
+        assert_eq!(atlas.tile(BlockType::GRASS, Face::Side), atlas.tile(BlockType::GRASS, Face::Bottom));
+        assert!(atlas.tile(BlockType::GRASS, Face::Side).is_some());
+        assert_eq!(atlas.tile(BlockType::STONE, Face::Top), None);
+    }
+}
@@ -0,0 +1,21 @@
+use crate::primitives::CrosshairVertex;
+use glium::{Display, VertexBuffer};
+
+// a quad covering the whole screen that the sky is drawn on before anything else
+pub struct Backdrop {
+    pub vbo: VertexBuffer<CrosshairVertex>
+}
+
+impl Backdrop {
+    pub fn new(display: &Display) -> Backdrop {
+        let corners: Vec<CrosshairVertex> = [[-1., -1.], [1., -1.], [-1., 1.], [1., 1.]]
+            .iter()
+            .map(|corner| CrosshairVertex { screen_position: *corner })
+            .collect();
+
+        let vbo = VertexBuffer::new(display, &corners).expect("to create vb");
+        Backdrop {
+            vbo
+        }
+    }
+}
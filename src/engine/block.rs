@@ -1,26 +1,211 @@
-use crate::primitives::InstanceAttr;
 
-#[derive(Clone, Debug, PartialEq)]
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BlockType {
     GRASS,
     SAND,
     DIRT,
     STONE,
     WATER,
+    TORCH,
+    WIRE,
+    LEVER,
+    BUTTON,
+    REPEATER,
+    INVERTER,
+    LAMP,
+    LIT_LAMP,
+    DOOR,
+    OPEN_DOOR,
 }
 
 impl BlockType {
-    fn color(&self) -> (f32, f32, f32, f32) {
+    pub const ALL: [BlockType; 15] = [
+        BlockType::GRASS,
+        BlockType::SAND,
+        BlockType::DIRT,
+        BlockType::STONE,
+        BlockType::WATER,
+        BlockType::TORCH,
+        BlockType::WIRE,
+        BlockType::LEVER,
+        BlockType::BUTTON,
+        BlockType::REPEATER,
+        BlockType::INVERTER,
+        BlockType::LAMP,
+        BlockType::LIT_LAMP,
+        BlockType::DOOR,
+        BlockType::OPEN_DOOR,
+    ];
+
+    // closest block colour to an 8-bit rgb sample, used by the heightmap material map, which
+    // never places lights or circuits
+    pub fn nearest_color(rgb: (u8, u8, u8)) -> BlockType {
+        let sample = (rgb.0 as f32 / 255., rgb.1 as f32 / 255., rgb.2 as f32 / 255.);
+        let distance = |b: &BlockType| {
+            let (r, g, b, _) = b.color();
+            (r - sample.0).powi(2) + (g - sample.1).powi(2) + (b - sample.2).powi(2)
+        };
+        BlockType::ALL
+            .iter()
+            .filter(|b| b.emission() == 0 && !b.is_circuit())
+            .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap())
+            .copied()
+            .unwrap()
+    }
+
+    // stable identifier used in save files and data files
+    pub fn id(&self) -> &'static str {
+        match *self {
+            BlockType::GRASS => "grass",
+            BlockType::SAND => "sand",
+            BlockType::DIRT => "dirt",
+            BlockType::STONE => "stone",
+            BlockType::WATER => "water",
+            BlockType::TORCH => "torch",
+            BlockType::WIRE => "wire",
+            BlockType::LEVER => "lever",
+            BlockType::BUTTON => "button",
+            BlockType::REPEATER => "repeater",
+            BlockType::INVERTER => "inverter",
+            BlockType::LAMP => "lamp",
+            BlockType::LIT_LAMP => "lit_lamp",
+            BlockType::DOOR => "door",
+            BlockType::OPEN_DOOR => "open_door",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<BlockType> {
+        BlockType::ALL.iter().find(|b| b.id() == id).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BlockType::GRASS => "Grass",
+            BlockType::SAND => "Sand",
+            BlockType::DIRT => "Dirt",
+            BlockType::STONE => "Stone",
+            BlockType::WATER => "Water",
+            BlockType::TORCH => "Torch",
+            BlockType::WIRE => "Wire",
+            BlockType::LEVER => "Lever",
+            BlockType::BUTTON => "Button",
+            BlockType::REPEATER => "Repeater",
+            BlockType::INVERTER => "Inverter",
+            BlockType::LAMP | BlockType::LIT_LAMP => "Lamp",
+            BlockType::DOOR | BlockType::OPEN_DOOR => "Door",
+        }
+    }
+
+    // seconds to break by hand, None for blocks that can't be mined
+    pub fn hardness(&self) -> Option<f32> {
+        match *self {
+            BlockType::GRASS => Some(0.6),
+            BlockType::SAND => Some(0.5),
+            BlockType::DIRT => Some(0.5),
+            BlockType::STONE => Some(1.5),
+            BlockType::WATER => None,
+            BlockType::TORCH => Some(0.1),
+            BlockType::WIRE | BlockType::LEVER | BlockType::BUTTON => Some(0.2),
+            BlockType::REPEATER | BlockType::INVERTER => Some(0.2),
+            BlockType::LAMP | BlockType::LIT_LAMP => Some(0.3),
+            BlockType::DOOR | BlockType::OPEN_DOOR => Some(1.0),
+        }
+    }
+
+    // what the block is picked up and offered as, lit lamps and open doors are the same item
+    pub fn item(&self) -> BlockType {
+        match *self {
+            BlockType::LIT_LAMP => BlockType::LAMP,
+            BlockType::OPEN_DOOR => BlockType::DOOR,
+            _ => *self,
+        }
+    }
+
+    // anything the circuit subsystem reads or drives
+    pub fn is_circuit(&self) -> bool {
+        match *self {
+            BlockType::WIRE | BlockType::LEVER | BlockType::BUTTON | BlockType::REPEATER | BlockType::INVERTER => true,
+            BlockType::LAMP | BlockType::LIT_LAMP | BlockType::DOOR | BlockType::OPEN_DOOR => true,
+            _ => false,
+        }
+    }
+
+    // every liquid behaviour (physics, visibility, underwater fog) is keyed off this
+    pub fn liquid(&self) -> Option<Liquid> {
+        match *self {
+            BlockType::WATER => Some(Liquid {
+                drag: 4.0,
+                buoyancy: 0.8,
+                fog_color: (0.10, 0.25, 0.45),
+                fog_density: 0.12,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn is_liquid(&self) -> bool {
+        self.liquid().is_some()
+    }
+
+    // whether entities collide with the block
+    pub fn is_solid(&self) -> bool {
+        !self.is_liquid() && *self != BlockType::OPEN_DOOR
+    }
+
+    // light level given off, 0 for blocks that don't glow
+    pub fn emission(&self) -> u8 {
+        match *self {
+            BlockType::TORCH => 14,
+            BlockType::LIT_LAMP => 15,
+            _ => 0,
+        }
+    }
+
+    // whether the block drops down when there's nothing solid underneath it
+    pub fn falls(&self) -> bool {
+        *self == BlockType::SAND
+    }
+
+    // whether blocks behind this one can be seen through it
+    pub fn is_transparent(&self) -> bool {
+        self.is_liquid() || *self == BlockType::OPEN_DOOR
+    }
+
+    // blended over what's behind it, so it has to be drawn after everything opaque
+    pub fn is_translucent(&self) -> bool {
+        self.color().3 < 1.
+    }
+
+    pub fn color(&self) -> (f32, f32, f32, f32) {
         match *self {
             BlockType::GRASS => (53. / 255., 170. / 255., 70. / 255., 1.),
             BlockType::SAND => (223. / 255., 223. / 255., 160. / 255., 1.),
             BlockType::DIRT => (94. / 255., 227. / 255., 230. / 255., 1.),
             BlockType::STONE => (170. / 255., 170. / 255., 185. / 255., 1.),
             BlockType::WATER => (64. / 255., 164. / 255., 223. / 255., 0.95),
+            BlockType::TORCH => (1., 200. / 255., 90. / 255., 1.),
+            BlockType::WIRE => (150. / 255., 24. / 255., 20. / 255., 1.),
+            BlockType::LEVER => (120. / 255., 90. / 255., 60. / 255., 1.),
+            BlockType::BUTTON => (140. / 255., 140. / 255., 150. / 255., 1.),
+            BlockType::REPEATER => (190. / 255., 70. / 255., 60. / 255., 1.),
+            BlockType::INVERTER => (110. / 255., 60. / 255., 150. / 255., 1.),
+            BlockType::LAMP => (95. / 255., 75. / 255., 45. / 255., 1.),
+            BlockType::LIT_LAMP => (1., 230. / 255., 150. / 255., 1.),
+            BlockType::DOOR => (130. / 255., 90. / 255., 50. / 255., 1.),
+            BlockType::OPEN_DOOR => (130. / 255., 90. / 255., 50. / 255., 0.3),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Liquid {
+    pub drag: f32, // fraction of velocity lost per second
+    pub buoyancy: f32, // fraction of gravity cancelled out
+    pub fog_color: (f32, f32, f32),
+    pub fog_density: f32,
+}
+
 #[derive(Clone, Debug)]
 pub struct Block {
     pub position: (f32, f32, f32), // in world space
@@ -35,12 +220,3 @@ impl Block {
         glm::vec3(result[0], result[1], result[2])
     }
 }
-
-impl Into<InstanceAttr> for Block {
-    fn into(self) -> InstanceAttr {
-        InstanceAttr {
-            world_position: self.position,
-            color: self.block_type.color(),
-        }
-    }
-}
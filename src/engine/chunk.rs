@@ -1,14 +1,14 @@
 use crate::engine::block::{Block, BlockType};
-use crate::primitives::InstanceAttr;
-use glium::vertex::PerInstance;
+use crate::engine::light::{Channel, LightMap};
+use crate::engine::mesh::{self, SectionVertices};
+use crate::engine::visibility::{self, Connectivity, SECTION_SIZE, TOP_SECTION};
+use crate::primitives::TextureVertex;
 use glium::{Display, VertexBuffer};
+use glm::{IVec3, Vec3};
 use nalgebra::{Point2, Point3};
 use crate::config::HEIGHT_OFFSET;
 use std::collections::HashSet;
-use std::collections::HashMap;
-use super::march::VoxelMarch;
-
-use glm::{IVec3, vec3};
+use std::collections::{BTreeMap, HashMap};
 
 pub const CHUNK_SIZE: i32 = 16;
 
@@ -18,14 +18,22 @@ pub type BlockCoordinate = Point3<i32>; // block space in a chunk (CHUNK_LENGTH
 pub struct Chunk {
     pub coordinates: ChunkCoordinate, //in chunk space, so (0, 0) is the chunk from worldspace (0,y,0) to (16,y,16);
     blocks: HashMap<BlockCoordinate, Block>, 
+    levels: HashMap<BlockCoordinate, u8>, // liquid that has flowed, sources have no entry
+    states: HashMap<BlockCoordinate, u8>, // switch positions and facings, 0 has no entry
+    light: LightMap,
     visible: HashSet<BlockCoordinate>,
-    vbo: Option<VertexBuffer<InstanceAttr>>,
+    meshes: Option<BTreeMap<i32, SectionMesh>>, // by section, None until (re)built
+    connectivity: Vec<Connectivity>, // by section, sections above the last are open
 }
 
-#[derive(Debug)]
-pub enum IntersectAction {
-    Add,
-    Remove
+// The faces of one section of the chunk and a world space box around them, for culling.
+// Translucent faces are kept apart so they can be drawn after everything opaque.
+pub struct SectionMesh {
+    pub opaque: Option<VertexBuffer<TextureVertex>>,
+    pub translucent: Option<VertexBuffer<TextureVertex>>, // back to front from sorted_from
+    faces: Vec<TextureVertex>, // the translucent faces, kept to sort again as the camera moves
+    sorted_from: Option<IVec3>, // block the camera was in when they were last sorted
+    pub bounds: (Vec3, Vec3),
 }
 
 unsafe impl Send for Chunk {}
@@ -35,8 +43,12 @@ impl Chunk {
         let c = Chunk {
             coordinates,
             blocks: HashMap::new(),
+            levels: HashMap::new(),
+            states: HashMap::new(),
+            light: LightMap::new(),
             visible: HashSet::new(),
-            vbo: None,
+            meshes: None,
+            connectivity: Vec::new(),
         };
         // c.add_plane();
         c
@@ -77,7 +89,7 @@ impl Chunk {
             //     continue;
             // }
 
-            if block.block_type == BlockType::WATER {
+            if block.block_type.is_transparent() {
                 self.visible.insert(*coordinate);
                 continue;
             }
@@ -99,7 +111,7 @@ impl Chunk {
                         break;
                     },
                     Some(b) => {
-                        if b.block_type == BlockType::WATER {
+                        if b.block_type.is_transparent() {
                             visible = true;
                             break;
                         }
@@ -139,44 +151,87 @@ impl Chunk {
         // });
     }
 
+    pub fn get(&self, coordinate: &BlockCoordinate) -> Option<&Block> {
+        self.blocks.get(coordinate)
+    }
+
     pub fn world_origin(&self) -> Point3<i32> {
         let world = self.coordinates * 16;
         [world[0], 0, world[1]].into()
     }
 
-    fn get_rendered(&self) -> Vec<InstanceAttr> {
-        // TODO only get visible
-        self.blocks.clone().into_iter().filter(|(pos, _)| self.visible.contains(&pos)).map(|(_, b)| b.into()).collect()
+    // blocks with at least one face that can be seen
+    pub fn rendered(&self) -> Vec<&Block> {
+        self.blocks.iter().filter(|(pos, _)| self.visible.contains(pos)).map(|(_, b)| b).collect()
     }
 
-    fn in_chunk(&self, block: &BlockCoordinate) -> bool {
-        (0..CHUNK_SIZE).contains(&block[0]) &&
-        (0..CHUNK_SIZE).contains(&block[2])
+    pub fn emitters(&self) -> Vec<(BlockCoordinate, u8)> {
+        self.blocks
+            .iter()
+            .map(|(coordinate, block)| (*coordinate, block.block_type.emission()))
+            .filter(|(_, emission)| *emission > 0)
+            .collect()
     }
 
-    fn to_chunk_coords(&self, block: &IVec3) -> BlockCoordinate {
-        let origin = self.coordinates * CHUNK_SIZE;
-        let block = block - vec3(origin.x, HEIGHT_OFFSET, origin.y);
-        block.into()
+    pub fn light(&self, coordinate: &BlockCoordinate, channel: Channel) -> u8 {
+        self.light.get(coordinate, channel)
     }
 
-    pub fn intersect(&mut self, ray: &mut VoxelMarch, action: &IntersectAction) -> bool {
-        loop {
-            let (block, normal) = ray.next().unwrap();
-            let pos = self.to_chunk_coords(&block);
-            if !self.in_chunk(&pos) { return false } else {
-                if self.blocks.contains_key(&pos) {
-                    println!("{:?} {}", action, block);
-                    match action {
-                        IntersectAction::Remove => {self.blocks.remove(&pos); },
-                        IntersectAction::Add => { self.add_block(pos + normal, BlockType::STONE); }
-                    };
-                    self.update_neighbors(&pos);
-                    self.vbo = None;
-                    return true
-                }
-            }
+    // false if the cell is outside the lit column, a change means the vbo has to be rebuilt
+    pub fn set_light(&mut self, coordinate: &BlockCoordinate, channel: Channel, value: u8) -> bool {
+        if self.light.get(coordinate, channel) != value {
+            self.meshes = None;
+        }
+        self.light.set(coordinate, channel, value)
+    }
+
+    pub fn level(&self, coordinate: &BlockCoordinate) -> Option<u8> {
+        self.levels.get(coordinate).copied()
+    }
+
+    pub fn set_level(&mut self, coordinate: BlockCoordinate, level: Option<u8>) {
+        match level {
+            Some(level) => self.levels.insert(coordinate, level),
+            None => self.levels.remove(&coordinate),
+        };
+    }
+
+    pub fn state(&self, coordinate: &BlockCoordinate) -> u8 {
+        self.states.get(coordinate).copied().unwrap_or(0)
+    }
+
+    pub fn set_state(&mut self, coordinate: BlockCoordinate, state: u8) {
+        if state == 0 {
+            self.states.remove(&coordinate);
+        } else {
+            self.states.insert(coordinate, state);
+        }
+    }
+
+    // blocks of the types picked out, e.g. to find the circuits in a freshly loaded chunk
+    pub fn blocks_where(&self, predicate: impl Fn(BlockType) -> bool) -> Vec<&Block> {
+        self.blocks.values().filter(|b| predicate(b.block_type)).collect()
+    }
+
+    // places a block after generation, keeping visibility and the vbo in sync
+    pub fn set_block(&mut self, coordinate: BlockCoordinate, block_type: BlockType) {
+        self.add_block(coordinate, block_type);
+        self.levels.remove(&coordinate);
+        self.states.remove(&coordinate);
+        self.visible.insert(coordinate);
+        self.meshes = None;
+    }
+
+    pub fn remove_block(&mut self, coordinate: &BlockCoordinate) -> Option<BlockType> {
+        let removed = self.blocks.remove(coordinate).map(|b| b.block_type);
+        if removed.is_some() {
+            self.levels.remove(coordinate);
+            self.states.remove(coordinate);
+            self.visible.remove(coordinate);
+            self.update_neighbors(coordinate);
+            self.meshes = None;
         }
+        removed
     }
 
     // pub fn add_plane(&mut self) {
@@ -197,20 +252,120 @@ impl Chunk {
     //     }
     // }
 
-    pub fn update_vbo(&mut self, display: &Display) {
-        if self.vbo.is_none() {
-            let instances: Vec<InstanceAttr> = self.get_rendered();
+    // one "block x y z id" line per block, then a "level x y z n" line per flowing liquid and
+    // a "state x y z n" line per block with a state
+    pub fn save(&self) -> String {
+        let mut data = String::new();
+        for (coordinate, block) in self.blocks.iter() {
+            data.push_str(&format!("block {} {} {} {}\n", coordinate[0], coordinate[1], coordinate[2], block.block_type.id()));
+        }
+        for (coordinate, level) in self.levels.iter() {
+            data.push_str(&format!("level {} {} {} {}\n", coordinate[0], coordinate[1], coordinate[2], level));
+        }
+        for (coordinate, state) in self.states.iter() {
+            data.push_str(&format!("state {} {} {} {}\n", coordinate[0], coordinate[1], coordinate[2], state));
+        }
+        data
+    }
+
+    // rebuilds a chunk from the block, level and state lines written by save, other lines are
+    // ignored
+    pub fn load(coordinates: ChunkCoordinate, data: &str) -> Chunk {
+        let mut chunk = Chunk::new(coordinates);
+        for line in data.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if let ["block", x, y, z, id] = fields[..] {
+                let position = (x.parse(), y.parse(), z.parse());
+                if let ((Ok(x), Ok(y), Ok(z)), Some(block_type)) = (position, BlockType::from_id(id)) {
+                    chunk.add_block([x, y, z].into(), block_type);
+                }
+            } else if let ["level", x, y, z, level] = fields[..] {
+                if let (Ok(x), Ok(y), Ok(z), Ok(level)) = (x.parse(), y.parse(), z.parse(), level.parse()) {
+                    chunk.set_level([x, y, z].into(), Some(level));
+                }
+            } else if let ["state", x, y, z, state] = fields[..] {
+                if let (Ok(x), Ok(y), Ok(z), Ok(state)) = (x.parse(), y.parse(), z.parse(), state.parse()) {
+                    chunk.set_state([x, y, z].into(), state);
+                }
+            }
+        }
+        chunk
+    }
+
+    // the world builds the mesh since its lighting and occlusion can depend on neighbouring chunks
+    pub fn needs_vbo(&self) -> bool {
+        self.meshes.is_none()
+    }
+
+    pub fn invalidate(&mut self) {
+        self.meshes = None;
+    }
 
-            println!("{} instances", instances.len());
+    // takes the chunk's faces split up by section, sections without any are left out
+    pub fn set_vbo(&mut self, display: &Display, sections: BTreeMap<i32, SectionVertices>) {
+        let mut meshes = BTreeMap::new();
+        for (section, vertices) in sections {
+            let bounds = match Self::mesh_bounds(vertices.opaque.iter().chain(vertices.translucent.iter())) {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            let buffer = |vertices: &[TextureVertex]| Some(VertexBuffer::new(display, vertices).expect("to create vb"));
+            let opaque = if vertices.opaque.is_empty() { None } else { buffer(&vertices.opaque) };
+            // rewritten in place each time the faces are sorted
+            let translucent = if vertices.translucent.is_empty() {
+                None
+            } else {
+                Some(VertexBuffer::dynamic(display, &vertices.translucent).expect("to create vb"))
+            };
+            meshes.insert(section, SectionMesh { opaque, translucent, faces: vertices.translucent, sorted_from: None, bounds });
+        }
+        self.meshes = Some(meshes);
+        self.update_connectivity();
+    }
 
-            let vbo = VertexBuffer::new(display, &instances).expect("to create vb");
+    fn mesh_bounds<'a>(mut vertices: impl Iterator<Item = &'a TextureVertex>) -> Option<(Vec3, Vec3)> {
+        let first = Vec3::from(vertices.next()?.position);
+        Some(vertices.fold((first, first), |(min, max), v| {
+            let position = Vec3::from(v.position);
+            (glm::min2(&min, &position), glm::max2(&max, &position))
+        }))
+    }
 
-            self.vbo = Some(vbo);
+    // puts translucent faces back in order whenever the camera moves into another block
+    pub fn sort_translucent(&mut self, eye: &Vec3, block: IVec3) {
+        for section in self.meshes.iter_mut().flat_map(|meshes| meshes.values_mut()) {
+            if let Some(translucent) = &section.translucent {
+                if section.sorted_from != Some(block) {
+                    mesh::sort_back_to_front(&mut section.faces, eye);
+                    translucent.write(&section.faces);
+                    section.sorted_from = Some(block);
+                }
+            }
         }
     }
 
-    //loads instance if they don't exist
-    pub fn per_instance(&self) -> Option<PerInstance> {
-        self.vbo.as_ref().and_then(|vbo| vbo.per_instance().ok())
+    pub fn meshes(&self) -> impl Iterator<Item = (i32, &SectionMesh)> {
+        self.meshes.iter().flat_map(|meshes| meshes.iter().map(|(section, mesh)| (*section, mesh)))
+    }
+
+    // recomputed with the mesh, since both change whenever a block does
+    pub fn update_connectivity(&mut self) {
+        let top = self.blocks.keys().map(|c| visibility::section_of(c[1] + HEIGHT_OFFSET)).max().unwrap_or(-1);
+        self.connectivity = (0..=top.min(TOP_SECTION))
+            .map(|section| {
+                let bottom = section * SECTION_SIZE - HEIGHT_OFFSET;
+                Connectivity::compute(|cell| {
+                    let coordinate = [cell.x, bottom + cell.y, cell.z].into();
+                    self.blocks.get(&coordinate).map_or(false, |b| !b.block_type.is_transparent())
+                })
+            })
+            .collect();
+    }
+
+    pub fn connectivity(&self, section: i32) -> Connectivity {
+        if section < 0 {
+            return Connectivity::CLOSED;
+        }
+        self.connectivity.get(section as usize).copied().unwrap_or(Connectivity::OPEN)
     }
 }
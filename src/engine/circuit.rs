@@ -0,0 +1,418 @@
+use crate::engine::block::BlockType;
+use crate::engine::chunk::ChunkCoordinate;
+use crate::engine::world::World;
+use glm::{vec3, IVec3, Vec3};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// strength a switch or diode drives a wire with, each further wire carries one less
+pub const MAX_SIGNAL: u8 = 15;
+// ticks between a repeater or inverter's input changing and its output following
+pub const DIODE_DELAY: u64 = 2;
+// ticks a pressed button stays on
+pub const BUTTON_TICKS: u64 = 20;
+
+const SIDES: [(i32, i32, i32); 6] = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+
+// Block states. Switches are on or off, diodes keep the direction they face in the low two
+// bits and whether their output is on above that.
+const ON: u8 = 1;
+const FACINGS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const FACING_MASK: u8 = 3;
+const POWERED: u8 = 4;
+
+// Wire strengths, worked out from the switches and diodes feeding each wire network, and
+// the blocks changed since the networks around them were last worked out. Strengths only
+// live here, a loaded chunk gets its circuits worked out again.
+pub struct Circuits {
+    signals: HashMap<IVec3, u8>,
+    dirty: Vec<IVec3>,
+}
+
+impl Circuits {
+    pub fn new() -> Circuits {
+        Circuits {
+            signals: HashMap::new(),
+            dirty: Vec::new(),
+        }
+    }
+
+    // strength of the wire at a block, 0 for anything else
+    pub fn signal(&self, block: &IVec3) -> u8 {
+        self.signals.get(block).copied().unwrap_or(0)
+    }
+
+    // the block changed, the circuits around it are updated on the next tick
+    pub fn touch(&mut self, block: &IVec3) {
+        self.dirty.push(*block);
+    }
+
+    pub fn unload_chunk(&mut self, coordinate: &ChunkCoordinate) {
+        self.signals.retain(|block, _| World::locate(block).0 != *coordinate);
+    }
+}
+
+impl Default for Circuits {
+    fn default() -> Circuits {
+        Circuits::new()
+    }
+}
+
+// Runs once a tick, after the scheduled block updates. Every wire network next to a changed
+// block has its strengths worked out again, then lamps, doors and diodes next to anything
+// that changed follow. Blocks are always visited sorted by position, so the same circuit
+// in the same state always updates the same way.
+pub fn update(world: &mut World) {
+    let mut dirty = std::mem::take(&mut world.circuits_mut().dirty);
+    if dirty.is_empty() {
+        return;
+    }
+    sort(&mut dirty);
+
+    let mut affected = Vec::new();
+    let mut network = Vec::new();
+    let mut seen = HashSet::new();
+    for block in dirty.iter() {
+        if !is_wire(world, block) {
+            world.circuits_mut().signals.remove(block);
+        }
+        for around in around(block) {
+            affected.push(around);
+            if is_wire(world, &around) && seen.insert(around) {
+                flood(world, around, &mut network, &mut seen);
+            }
+        }
+    }
+
+    // wires fed directly start at full strength, then each level spreads a step weaker to
+    // the wires next to it that aren't already stronger
+    let mut strength: HashMap<IVec3, u8> = network.iter().map(|wire| (*wire, fed(world, wire))).collect();
+    for level in (2..=MAX_SIGNAL).rev() {
+        for wire in network.iter() {
+            if strength[wire] != level {
+                continue;
+            }
+            for side in sides(wire) {
+                if let Some(weaker) = strength.get_mut(&side).filter(|s| **s < level - 1) {
+                    *weaker = level - 1;
+                }
+            }
+        }
+    }
+
+    let circuits = world.circuits_mut();
+    for wire in network.iter() {
+        if circuits.signal(wire) != strength[wire] {
+            circuits.signals.insert(*wire, strength[wire]);
+            affected.extend(sides(wire));
+        }
+    }
+
+    sort(&mut affected);
+    for block in affected.iter() {
+        drive(world, block);
+    }
+}
+
+// Flips a lever or presses a button, false for blocks that can't be used.
+pub fn interact(world: &mut World, block: &IVec3) -> bool {
+    match world.get_block(block) {
+        Some(BlockType::LEVER) => {
+            let state = world.block_state(block) ^ ON;
+            world.set_block_state(block, state);
+            true
+        }
+        Some(BlockType::BUTTON) => {
+            world.set_block_state(block, ON);
+            world.schedule_tick(block, BUTTON_TICKS);
+            true
+        }
+        _ => false,
+    }
+}
+
+// Turns a freshly placed diode to face the way the player is looking.
+pub fn orient(world: &mut World, block: &IVec3, view: &Vec3) {
+    if !is_diode(world.get_block(block)) {
+        return;
+    }
+    let facing = if view.x.abs() >= view.z.abs() {
+        if view.x > 0. { 0 } else { 2 }
+    } else if view.z > 0. {
+        1
+    } else {
+        3
+    };
+    world.set_block_state(block, facing);
+}
+
+// a diode's output follows its input (inverted for an inverter) DIODE_DELAY ticks later
+pub fn diode_tick(world: &mut World, block: &IVec3) {
+    let state = world.block_state(block);
+    let powered = diode_output(world, block);
+    if powered != (state & POWERED != 0) {
+        world.set_block_state(block, (state & FACING_MASK) | if powered { POWERED } else { 0 });
+    }
+}
+
+pub fn button_tick(world: &mut World, block: &IVec3) {
+    world.set_block_state(block, 0);
+}
+
+// lamps light and doors open while powered, diodes are woken when their input changes
+fn drive(world: &mut World, block: &IVec3) {
+    match world.get_block(block).copied() {
+        Some(BlockType::LAMP) | Some(BlockType::LIT_LAMP) => {
+            let lamp = if powered(world, block) { BlockType::LIT_LAMP } else { BlockType::LAMP };
+            if world.get_block(block) != Some(&lamp) {
+                world.set_block(block, lamp);
+            }
+        }
+        Some(BlockType::DOOR) | Some(BlockType::OPEN_DOOR) => {
+            let door = if powered(world, block) { BlockType::OPEN_DOOR } else { BlockType::DOOR };
+            if world.get_block(block) != Some(&door) {
+                world.set_block(block, door);
+            }
+        }
+        diode if is_diode(diode.as_ref()) => {
+            if diode_output(world, block) != (world.block_state(block) & POWERED != 0) {
+                world.schedule_tick(block, DIODE_DELAY);
+            }
+        }
+        _ => (),
+    }
+}
+
+// what a switch or diode sends into the block next to it, wires are handled by update
+fn power_into(world: &World, from: &IVec3, to: &IVec3) -> u8 {
+    let state = world.block_state(from);
+    match world.get_block(from) {
+        Some(BlockType::LEVER) | Some(BlockType::BUTTON) if state & ON != 0 => MAX_SIGNAL,
+        diode if is_diode(diode) && state & POWERED != 0 && front(from, state) == *to => MAX_SIGNAL,
+        _ => 0,
+    }
+}
+
+// a consumer is on when any block next to it is a live wire or is powering it
+fn powered(world: &World, block: &IVec3) -> bool {
+    sides(block).iter().any(|side| world.circuits().signal(side) > 0 || power_into(world, side, block) > 0)
+}
+
+// what a diode's output should be given its input, diodes only listen to the block behind them
+fn diode_output(world: &World, block: &IVec3) -> bool {
+    let back = block - (front(block, world.block_state(block)) - block);
+    let input = world.circuits().signal(&back) > 0 || power_into(world, &back, block) > 0;
+    input != (world.get_block(block) == Some(&BlockType::INVERTER))
+}
+
+fn fed(world: &World, wire: &IVec3) -> u8 {
+    sides(wire).iter().map(|side| power_into(world, side, wire)).max().unwrap_or(0)
+}
+
+fn front(block: &IVec3, state: u8) -> IVec3 {
+    let (x, z) = FACINGS[(state & FACING_MASK) as usize];
+    block + vec3(x, 0, z)
+}
+
+// every wire connected to the start, in the order they were reached
+fn flood(world: &World, start: IVec3, network: &mut Vec<IVec3>, seen: &mut HashSet<IVec3>) {
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    while let Some(wire) = queue.pop_front() {
+        network.push(wire);
+        for side in sides(&wire) {
+            if is_wire(world, &side) && seen.insert(side) {
+                queue.push_back(side);
+            }
+        }
+    }
+}
+
+fn is_wire(world: &World, block: &IVec3) -> bool {
+    world.get_block(block) == Some(&BlockType::WIRE)
+}
+
+fn is_diode(block: Option<&BlockType>) -> bool {
+    block == Some(&BlockType::REPEATER) || block == Some(&BlockType::INVERTER)
+}
+
+fn sides(block: &IVec3) -> Vec<IVec3> {
+    SIDES.iter().map(|(x, y, z)| block + vec3(*x, *y, *z)).collect()
+}
+
+// the block and the six next to it
+fn around(block: &IVec3) -> Vec<IVec3> {
+    let mut blocks = sides(block);
+    blocks.push(*block);
+    blocks
+}
+
+fn sort(blocks: &mut Vec<IVec3>) {
+    blocks.sort_by_key(|b| (b.x, b.y, b.z));
+    blocks.dedup();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(world: &mut World, ticks: u64) {
+        for _ in 0..ticks {
+            world.tick(1. / 60.);
+        }
+    }
+
+    fn wire(world: &mut World, from: IVec3, to: IVec3) {
+        for x in from.x.min(to.x)..=from.x.max(to.x) {
+            for z in from.z.min(to.z)..=from.z.max(to.z) {
+                world.set_block(&vec3(x, from.y, z), BlockType::WIRE);
+            }
+        }
+    }
+
+    fn place(world: &mut World, block: IVec3, block_type: BlockType) -> IVec3 {
+        world.set_block(&block, block_type);
+        block
+    }
+
+    #[test]
+    fn test_lever_lights_a_lamp_through_wire() {
+        let mut world = World::flat(0, true);
+        let lever = place(&mut world, vec3(2, 17, 2), BlockType::LEVER);
+        wire(&mut world, vec3(3, 17, 2), vec3(6, 17, 2));
+        let lamp = place(&mut world, vec3(7, 17, 2), BlockType::LAMP);
+        run(&mut world, 1);
+        assert_eq!(world.get_block(&lamp), Some(&BlockType::LAMP));
+
+        assert!(interact(&mut world, &lever));
+        run(&mut world, 1);
+        assert_eq!(world.circuits().signal(&vec3(3, 17, 2)), MAX_SIGNAL);
+        assert_eq!(world.circuits().signal(&vec3(6, 17, 2)), MAX_SIGNAL - 3);
+        assert_eq!(world.get_block(&lamp), Some(&BlockType::LIT_LAMP));
+        assert_eq!(world.block_light(&vec3(7, 18, 2)), 14);
+
+        interact(&mut world, &lever);
+        run(&mut world, 1);
+        assert_eq!(world.circuits().signal(&vec3(3, 17, 2)), 0);
+        assert_eq!(world.get_block(&lamp), Some(&BlockType::LAMP));
+    }
+
+    #[test]
+    fn test_signal_fades_with_distance() {
+        let mut world = World::flat(0, true);
+        let lever = place(&mut world, vec3(0, 17, 0), BlockType::LEVER);
+        wire(&mut world, vec3(1, 17, 0), vec3(15, 17, 0));
+        wire(&mut world, vec3(15, 17, 1), vec3(15, 17, 3));
+        let near = place(&mut world, vec3(14, 17, 1), BlockType::LAMP);
+        let far = place(&mut world, vec3(15, 17, 4), BlockType::LAMP);
+        interact(&mut world, &lever);
+        run(&mut world, 1);
+
+        for x in 1..16 {
+            assert_eq!(world.circuits().signal(&vec3(x, 17, 0)), MAX_SIGNAL + 1 - x as u8);
+        }
+        assert_eq!(world.circuits().signal(&vec3(15, 17, 1)), 0);
+        assert_eq!(world.get_block(&near), Some(&BlockType::LIT_LAMP));
+        assert_eq!(world.get_block(&far), Some(&BlockType::LAMP));
+    }
+
+    #[test]
+    fn test_strongest_feed_wins() {
+        let mut world = World::flat(0, true);
+        let left = place(&mut world, vec3(2, 17, 6), BlockType::LEVER);
+        let right = place(&mut world, vec3(10, 17, 6), BlockType::LEVER);
+        wire(&mut world, vec3(3, 17, 6), vec3(9, 17, 6));
+        interact(&mut world, &left);
+        interact(&mut world, &right);
+        run(&mut world, 1);
+        assert_eq!(world.circuits().signal(&vec3(6, 17, 6)), MAX_SIGNAL - 3);
+        assert_eq!(world.circuits().signal(&vec3(8, 17, 6)), MAX_SIGNAL - 1);
+
+        // cutting the line leaves each half fed from its own end
+        world.remove_block(&vec3(5, 17, 6));
+        interact(&mut world, &right);
+        run(&mut world, 1);
+        assert_eq!(world.circuits().signal(&vec3(4, 17, 6)), MAX_SIGNAL - 1);
+        assert_eq!(world.circuits().signal(&vec3(6, 17, 6)), 0);
+    }
+
+    #[test]
+    fn test_repeater_delays_and_restores_the_signal() {
+        let mut world = World::flat(0, true);
+        let lever = place(&mut world, vec3(0, 17, 5), BlockType::LEVER);
+        wire(&mut world, vec3(1, 17, 5), vec3(3, 17, 5));
+        let repeater = place(&mut world, vec3(4, 17, 5), BlockType::REPEATER);
+        orient(&mut world, &repeater, &vec3(1., -0.2, 0.3));
+        let out = place(&mut world, vec3(5, 17, 5), BlockType::WIRE);
+        let lamp = place(&mut world, vec3(6, 17, 5), BlockType::LAMP);
+        run(&mut world, 5);
+
+        interact(&mut world, &lever);
+        run(&mut world, 1);
+        assert_eq!(world.circuits().signal(&vec3(3, 17, 5)), MAX_SIGNAL - 2);
+        assert_eq!(world.get_block(&lamp), Some(&BlockType::LAMP));
+        run(&mut world, DIODE_DELAY);
+        assert_eq!(world.circuits().signal(&out), MAX_SIGNAL);
+        assert_eq!(world.get_block(&lamp), Some(&BlockType::LIT_LAMP));
+
+        // only the block behind drives it, the output side doesn't feed back
+        interact(&mut world, &lever);
+        run(&mut world, 1 + DIODE_DELAY);
+        assert_eq!(world.get_block(&lamp), Some(&BlockType::LAMP));
+    }
+
+    #[test]
+    fn test_inverter_is_on_without_input() {
+        let mut world = World::flat(0, true);
+        let lever = place(&mut world, vec3(3, 17, 8), BlockType::LEVER);
+        let inverter = place(&mut world, vec3(4, 17, 8), BlockType::INVERTER);
+        let lamp = place(&mut world, vec3(5, 17, 8), BlockType::LAMP);
+        run(&mut world, 5);
+        assert_eq!(world.get_block(&lamp), Some(&BlockType::LIT_LAMP));
+
+        interact(&mut world, &lever);
+        run(&mut world, 5);
+        assert_eq!(world.block_state(&inverter) & POWERED, 0);
+        assert_eq!(world.get_block(&lamp), Some(&BlockType::LAMP));
+    }
+
+    #[test]
+    fn test_button_releases_itself() {
+        let mut world = World::flat(0, true);
+        let button = place(&mut world, vec3(2, 17, 12), BlockType::BUTTON);
+        let door = place(&mut world, vec3(3, 17, 12), BlockType::DOOR);
+        run(&mut world, 30);
+
+        assert!(interact(&mut world, &button));
+        run(&mut world, 1);
+        assert_eq!(world.get_block(&door), Some(&BlockType::OPEN_DOOR));
+        assert!(!world.is_solid(&door));
+        run(&mut world, BUTTON_TICKS);
+        assert_eq!(world.get_block(&door), Some(&BlockType::DOOR));
+        assert!(!interact(&mut world, &door));
+    }
+
+    // an inverter feeding its own input through a loop of wire is a clock
+    fn clock(world: &mut World) -> IVec3 {
+        place(world, vec3(8, 17, 8), BlockType::INVERTER);
+        wire(world, vec3(9, 17, 8), vec3(9, 17, 9));
+        wire(world, vec3(7, 17, 9), vec3(8, 17, 9));
+        place(world, vec3(7, 17, 8), BlockType::WIRE);
+        place(world, vec3(10, 17, 8), BlockType::LAMP)
+    }
+
+    #[test]
+    fn test_circuits_run_the_same_every_time() {
+        let lamps = || {
+            let mut world = World::flat(0, true);
+            let lamp = clock(&mut world);
+            (0..60).map(|_| {
+                run(&mut world, 1);
+                world.get_block(&lamp) == Some(&BlockType::LIT_LAMP)
+            }).collect::<Vec<bool>>()
+        };
+        let first = lamps();
+        assert_eq!(first, lamps());
+        let toggles = first.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!(toggles >= 10, "{:?}", first);
+    }
+}
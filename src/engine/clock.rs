@@ -0,0 +1,249 @@
+use crate::config::TICKS_PER_SECOND;
+use glm::{vec3, Vec3};
+use std::f32::consts::PI;
+
+// ticks in one day and night, twenty minutes
+pub const DAY_LENGTH: u64 = 20 * 60 * TICKS_PER_SECOND as u64;
+
+// named points in the day as fractions of it, the day starts at midnight
+pub const MIDNIGHT: f32 = 0.;
+pub const SUNRISE: f32 = 0.25;
+pub const NOON: f32 = 0.5;
+pub const SUNSET: f32 = 0.75;
+// new worlds start a little after sunrise
+const MORNING: f32 = 0.3;
+
+// how much of the sky's light is left at night, moonlight
+const NIGHT_AMBIENT: f32 = 0.2;
+// the sun's path leans a little towards +z so it never lines up with a block face
+const TILT: f32 = 0.25;
+
+const DAY_HORIZON: (f32, f32, f32) = (0.529, 0.808, 0.980);
+const DAY_ZENITH: (f32, f32, f32) = (0.24, 0.48, 0.92);
+const NIGHT_HORIZON: (f32, f32, f32) = (0.03, 0.04, 0.10);
+const NIGHT_ZENITH: (f32, f32, f32) = (0.005, 0.01, 0.04);
+const DUSK: (f32, f32, f32) = (0.95, 0.48, 0.28);
+const SUN_COLOR: (f32, f32, f32) = (1.0, 0.97, 0.9);
+const LOW_SUN_COLOR: (f32, f32, f32) = (1.0, 0.6, 0.35);
+const MOON_COLOR: (f32, f32, f32) = (0.35, 0.4, 0.55);
+
+// What the sky looks like at one moment. The light is the sun by day and the moon by night,
+// fading out as either nears the horizon so nothing jumps when they swap over.
+#[derive(Clone, Copy, Debug)]
+pub struct Sky {
+    pub sun_direction: Vec3, // towards the sun, below the horizon at night
+    pub light_direction: Vec3, // towards the sun or the moon, whichever is up
+    pub light_color: Vec3,
+    pub horizon: Vec3,
+    pub zenith: Vec3,
+    pub ambient: f32, // fraction of sky light that reaches the ground
+}
+
+// The world's time of day. It advances once a simulation tick unless frozen, and counts
+// whole days so it can be saved as a single number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clock {
+    ticks: u64,
+    frozen: bool,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock {
+            ticks: (MORNING * DAY_LENGTH as f32) as u64,
+            frozen: false,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if !self.frozen {
+            self.ticks += 1;
+        }
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn freeze(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    pub fn set_ticks(&mut self, ticks: u64) {
+        self.ticks = ticks;
+    }
+
+    // moves on to the next time the day reaches the given fraction, days never run backwards
+    pub fn set_time_of_day(&mut self, time: f32) {
+        let target = (time.rem_euclid(1.) * DAY_LENGTH as f32) as u64;
+        let start = self.ticks - self.ticks % DAY_LENGTH;
+        self.ticks = if start + target >= self.ticks { start + target } else { start + DAY_LENGTH + target };
+    }
+
+    pub fn day(&self) -> u64 {
+        self.ticks / DAY_LENGTH
+    }
+
+    // 0 at midnight, 0.5 at noon
+    pub fn time_of_day(&self) -> f32 {
+        (self.ticks % DAY_LENGTH) as f32 / DAY_LENGTH as f32
+    }
+
+    pub fn sky(&self) -> Sky {
+        // the sun rises in +x and sets in -x
+        let angle = (self.time_of_day() - SUNRISE) * 2. * PI;
+        let sun_direction = vec3(angle.cos(), angle.sin(), TILT).normalize();
+        let height = sun_direction.y;
+
+        let daylight = ramp(height, -0.1, 0.2);
+        let twilight = 1. - ramp(height.abs(), 0., 0.3);
+        let horizon = mix(&color(NIGHT_HORIZON), &color(DAY_HORIZON), daylight);
+        let horizon = mix(&horizon, &color(DUSK), twilight * 0.6);
+        let zenith = mix(&color(NIGHT_ZENITH), &color(DAY_ZENITH), daylight);
+
+        let (light_direction, light_color) = if height >= 0. {
+            (sun_direction, mix(&color(LOW_SUN_COLOR), &color(SUN_COLOR), ramp(height, 0., 0.4)))
+        } else {
+            (-sun_direction, color(MOON_COLOR))
+        };
+        Sky {
+            sun_direction,
+            light_direction,
+            light_color: light_color * ramp(height.abs(), 0., 0.25),
+            horizon,
+            zenith,
+            ambient: NIGHT_AMBIENT + (1. - NIGHT_AMBIENT) * daylight,
+        }
+    }
+
+    // "time <ticks> running" or "time <ticks> frozen", as kept in the world metadata
+    pub fn save(&self) -> String {
+        format!("time {} {}\n", self.ticks, if self.frozen { "frozen" } else { "running" })
+    }
+
+    pub fn load(metadata: &str) -> Option<Clock> {
+        metadata.lines().find_map(|line| match line.split_whitespace().collect::<Vec<&str>>()[..] {
+            ["time", ticks, state] => Some(Clock {
+                ticks: ticks.parse().ok()?,
+                frozen: state == "frozen",
+            }),
+            _ => None,
+        })
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Clock {
+        Clock::new()
+    }
+}
+
+// 0 below from, 1 above to, linear between
+fn ramp(x: f32, from: f32, to: f32) -> f32 {
+    ((x - from) / (to - from)).clamp(0., 1.)
+}
+
+fn mix(a: &Vec3, b: &Vec3, t: f32) -> Vec3 {
+    a + (b - a) * t
+}
+
+fn color((r, g, b): (f32, f32, f32)) -> Vec3 {
+    vec3(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::generator::PlanarGenerator;
+    use crate::engine::storage::ChunkStore;
+    use crate::engine::world::World;
+
+    fn at(time: f32) -> Clock {
+        let mut clock = Clock::new();
+        clock.set_ticks(0);
+        clock.set_time_of_day(time);
+        clock
+    }
+
+    #[test]
+    fn test_advances_unless_frozen() {
+        let mut clock = Clock::new();
+        let start = clock.ticks();
+        clock.tick();
+        assert_eq!(clock.ticks(), start + 1);
+        clock.freeze(true);
+        for _ in 0..100 {
+            clock.tick();
+        }
+        assert_eq!(clock.ticks(), start + 1);
+    }
+
+    #[test]
+    fn test_setting_the_time_never_goes_back_a_day() {
+        let mut clock = at(NOON);
+        assert_eq!(clock.day(), 0);
+        clock.set_time_of_day(SUNRISE);
+        assert_eq!(clock.day(), 1);
+        assert!((clock.time_of_day() - SUNRISE).abs() < 1e-4);
+        clock.set_time_of_day(SUNSET);
+        assert_eq!(clock.day(), 1);
+    }
+
+    #[test]
+    fn test_sun_is_up_by_day_and_down_at_night() {
+        let noon = at(NOON).sky();
+        assert!(noon.sun_direction.y > 0.9);
+        assert_eq!(noon.light_direction, noon.sun_direction);
+        assert!((noon.ambient - 1.).abs() < 1e-4);
+
+        let midnight = at(MIDNIGHT).sky();
+        assert!(midnight.sun_direction.y < -0.9);
+        assert!(midnight.light_direction.y > 0.9, "the moon is up");
+        assert!((midnight.ambient - NIGHT_AMBIENT).abs() < 1e-4);
+        assert!(midnight.zenith.norm() < noon.zenith.norm());
+
+        let sunrise = at(SUNRISE).sky();
+        assert!(sunrise.sun_direction.x > 0.9);
+        assert!(sunrise.light_color.norm() < 1e-4, "no directional light with the sun on the horizon");
+        assert!(sunrise.horizon.x > noon.horizon.x, "dawn is redder");
+    }
+
+    #[test]
+    fn test_light_changes_smoothly() {
+        let step = 1. / 1000.;
+        for i in 0..1000 {
+            let (a, b) = (at(i as f32 * step).sky(), at((i + 1) as f32 * step).sky());
+            let light = |s: &Sky| s.light_direction.component_mul(&s.light_color);
+            assert!((light(&a) - light(&b)).norm() < 0.05, "jump at {}", i as f32 * step);
+            assert!((a.ambient - b.ambient).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_saves_and_loads() {
+        let mut clock = at(SUNSET);
+        clock.freeze(true);
+        let metadata = format!("spawn 1 2 3\n{}", clock.save());
+        assert_eq!(Clock::load(&metadata), Some(clock));
+        assert_eq!(Clock::load("spawn 1 2 3\n"), None);
+    }
+
+    #[test]
+    fn test_world_keeps_its_time() {
+        let mut world = World::with_generator(PlanarGenerator::new());
+        let start = world.clock().ticks();
+        world.tick(1. / 60.);
+        assert_eq!(world.clock().ticks(), start + 1);
+
+        let mut store = ChunkStore::memory();
+        store.save_metadata("spawn 5 20 -3\ntime 100 frozen\n".to_string());
+        world.set_store(store);
+        world.tick(1. / 60.);
+        assert_eq!(world.clock().ticks(), 100);
+        assert!(world.clock().is_frozen());
+    }
+}
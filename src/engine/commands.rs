@@ -0,0 +1,87 @@
+use crate::engine::clock;
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeSetting {
+    OfDay(f32), // the next time the day reaches this fraction
+    Ticks(u64), // an absolute time since the world began
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    QueryTime,
+    SetTime(TimeSetting),
+    FreezeTime(bool),
+}
+
+// usage: time | time set <sunrise|day|noon|sunset|night|midnight|ticks> | time freeze | time resume
+pub fn parse(line: &str) -> Result<Command, String> {
+    match line.split_whitespace().collect::<Vec<&str>>()[..] {
+        ["time"] => Ok(Command::QueryTime),
+        ["time", "freeze"] => Ok(Command::FreezeTime(true)),
+        ["time", "resume"] => Ok(Command::FreezeTime(false)),
+        ["time", "set", value] => {
+            let setting = match value {
+                "sunrise" => TimeSetting::OfDay(clock::SUNRISE),
+                "day" => TimeSetting::OfDay(clock::SUNRISE + 0.05),
+                "noon" => TimeSetting::OfDay(clock::NOON),
+                "sunset" => TimeSetting::OfDay(clock::SUNSET),
+                "night" => TimeSetting::OfDay(clock::SUNSET + 0.05),
+                "midnight" => TimeSetting::OfDay(clock::MIDNIGHT),
+                ticks => TimeSetting::Ticks(ticks.parse().map_err(|_| format!("Unknown time: {}", ticks))?),
+            };
+            Ok(Command::SetTime(setting))
+        }
+        [] => Err("Empty command".to_string()),
+        _ => Err(format!("Unknown command: {}", line.trim())),
+    }
+}
+
+// Commands typed into the terminal the game was started from. Lines are read on their own
+// thread so waiting for input never holds up a frame.
+pub struct Console {
+    lines: Receiver<String>,
+}
+
+impl Console {
+    pub fn stdin() -> Console {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            // stops once stdin closes or the game has gone
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Console { lines }
+    }
+
+    // everything typed since the last call, parsed
+    pub fn poll(&self) -> Vec<Result<Command, String>> {
+        self.lines.try_iter().map(|line| parse(&line)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_time_commands() {
+        assert_eq!(parse("time"), Ok(Command::QueryTime));
+        assert_eq!(parse(" time  freeze "), Ok(Command::FreezeTime(true)));
+        assert_eq!(parse("time resume"), Ok(Command::FreezeTime(false)));
+        assert_eq!(parse("time set noon"), Ok(Command::SetTime(TimeSetting::OfDay(clock::NOON))));
+        assert_eq!(parse("time set 1200"), Ok(Command::SetTime(TimeSetting::Ticks(1200))));
+    }
+
+    #[test]
+    fn test_rejects_unknown_commands() {
+        assert!(parse("").is_err());
+        assert!(parse("time set later").is_err());
+        assert!(parse("weather clear").is_err());
+    }
+}
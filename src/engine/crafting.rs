@@ -0,0 +1,327 @@
+use crate::engine::block::BlockType;
+use crate::engine::inventory::ItemStack;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const GRID_SIZE: usize = 3; // cells along each side of the crafting grid
+pub const GRID_CELLS: usize = GRID_SIZE * GRID_SIZE;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    // rows top to bottom, trimmed to the smallest box around the ingredients
+    Shaped { width: usize, cells: Vec<Option<BlockType>> },
+    // sorted by id so the order ingredients were listed in doesn't matter
+    Shapeless(Vec<BlockType>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recipe {
+    pub output: ItemStack,
+    pub shape: Shape,
+}
+
+impl Recipe {
+    fn matches(&self, grid: &[Option<BlockType>]) -> bool {
+        match &self.shape {
+            Shape::Shaped { width, cells } => trim(grid, GRID_SIZE) == (*width, cells.clone()),
+            Shape::Shapeless(ingredients) => sorted(grid.iter().flatten().copied().collect()) == *ingredients,
+        }
+    }
+
+    // a few short lines for the recipe book
+    pub fn describe(&self) -> Vec<String> {
+        let abbreviation = |cell: &Option<BlockType>| cell.map_or("..", |b| &b.name()[..2]).to_string();
+        let mut lines = vec![format!("{} x{}", self.output.block.name(), self.output.count)];
+        match &self.shape {
+            Shape::Shaped { width, cells } => {
+                for row in cells.chunks(*width) {
+                    lines.push(row.iter().map(abbreviation).collect::<Vec<String>>().join(" "));
+                }
+            }
+            Shape::Shapeless(ingredients) => {
+                lines.push(ingredients.iter().map(|b| b.name()).collect::<Vec<&str>>().join(" + "));
+            }
+        }
+        lines
+    }
+}
+
+fn sorted(mut blocks: Vec<BlockType>) -> Vec<BlockType> {
+    blocks.sort_by_key(|b| b.id());
+    blocks
+}
+
+// cuts the empty rows and columns around a grid of the given width
+fn trim(cells: &[Option<BlockType>], width: usize) -> (usize, Vec<Option<BlockType>>) {
+    let filled: Vec<(usize, usize)> = (0..cells.len())
+        .filter(|i| cells[*i].is_some())
+        .map(|i| (i % width, i / width))
+        .collect();
+    if filled.is_empty() {
+        return (0, Vec::new());
+    }
+    let (left, right) = (filled.iter().map(|c| c.0).min().unwrap(), filled.iter().map(|c| c.0).max().unwrap());
+    let (top, bottom) = (filled.iter().map(|c| c.1).min().unwrap(), filled.iter().map(|c| c.1).max().unwrap());
+    let trimmed = (top..=bottom)
+        .flat_map(|row| (left..=right).map(move |column| cells[row * width + column]))
+        .collect();
+    (right - left + 1, trimmed)
+}
+
+// Every recipe known to the game. They only refer to blocks by id, so a block added to the
+// registry can be made craftable by adding lines to data/recipes.txt.
+pub struct Recipes {
+    recipes: Vec<Recipe>,
+}
+
+impl Recipes {
+    // the recipes shipped in data/recipes.txt, as they were when the game was built
+    pub fn builtin() -> Recipes {
+        Recipes::parse(include_str!("../../data/recipes.txt")).expect("to parse data/recipes.txt")
+    }
+
+    // reads recipes at startup so they can be edited without a rebuild, falling back to the
+    // built in ones when the file isn't there
+    pub fn load(file: &Path) -> Result<Recipes, String> {
+        match fs::read_to_string(file) {
+            Ok(text) => Recipes::parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Recipes::builtin()),
+            Err(e) => Err(format!("{}: {}", file.display(), e)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Recipes, String> {
+        let mut recipes = Vec::new();
+        // output, rows and keys of the shaped recipe being read
+        let mut shaped: Option<(ItemStack, Vec<Vec<char>>, Vec<(char, BlockType)>, usize)> = None;
+
+        for (n, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split('#').next().unwrap().split_whitespace().collect();
+            let error = |message: String| format!("line {}: {}", n + 1, message);
+            match fields[..] {
+                [] => (),
+                ["shaped", output, count] => {
+                    if let Some(recipe) = shaped.take() {
+                        recipes.push(Self::build_shaped(recipe)?);
+                    }
+                    shaped = Some((Self::stack(output, count).map_err(error)?, Vec::new(), Vec::new(), n + 1));
+                }
+                ["shapeless", output, count, ref ingredients @ ..] => {
+                    if let Some(recipe) = shaped.take() {
+                        recipes.push(Self::build_shaped(recipe)?);
+                    }
+                    let ingredients = ingredients
+                        .iter()
+                        .map(|id| Self::block(id))
+                        .collect::<Result<Vec<BlockType>, String>>()
+                        .map_err(error)?;
+                    if ingredients.is_empty() || ingredients.len() > GRID_CELLS {
+                        return Err(error(format!("shapeless recipes take 1 to {} ingredients", GRID_CELLS)));
+                    }
+                    recipes.push(Recipe {
+                        output: Self::stack(output, count).map_err(error)?,
+                        shape: Shape::Shapeless(sorted(ingredients)),
+                    });
+                }
+                ["row", symbols] => match shaped.as_mut() {
+                    Some((_, rows, _, _)) => rows.push(symbols.chars().collect()),
+                    None => return Err(error("row outside a shaped recipe".to_string())),
+                },
+                ["key", symbol, id] => match (shaped.as_mut(), symbol.chars().collect::<Vec<char>>().as_slice()) {
+                    (Some((_, _, keys, _)), [symbol]) => keys.push((*symbol, Self::block(id).map_err(error)?)),
+                    (None, _) => return Err(error("key outside a shaped recipe".to_string())),
+                    _ => return Err(error(format!("key {} is not a single character", symbol))),
+                },
+                _ => return Err(error(format!("can't read {}", line.trim()))),
+            }
+        }
+        if let Some(recipe) = shaped.take() {
+            recipes.push(Self::build_shaped(recipe)?);
+        }
+        Ok(Recipes { recipes })
+    }
+
+    fn build_shaped((output, rows, keys, line): (ItemStack, Vec<Vec<char>>, Vec<(char, BlockType)>, usize)) -> Result<Recipe, String> {
+        let error = |message: &str| Err(format!("line {}: {}", line, message));
+        let width = rows.first().map_or(0, |row| row.len());
+        if rows.is_empty() || rows.len() > GRID_SIZE || width > GRID_SIZE || rows.iter().any(|row| row.len() != width) {
+            return error("shaped recipes need 1 to 3 rows of the same length, up to 3 long");
+        }
+        let mut cells = Vec::new();
+        for symbol in rows.iter().flatten() {
+            match (symbol, keys.iter().find(|(key, _)| key == symbol)) {
+                ('.', _) => cells.push(None),
+                (_, Some((_, block))) => cells.push(Some(*block)),
+                (_, None) => return error(&format!("no key for {}", symbol)),
+            }
+        }
+        let (width, cells) = trim(&cells, width);
+        if cells.is_empty() {
+            return error("shaped recipe without ingredients");
+        }
+        Ok(Recipe {
+            output,
+            shape: Shape::Shaped { width, cells },
+        })
+    }
+
+    fn block(id: &str) -> Result<BlockType, String> {
+        BlockType::from_id(id).ok_or(format!("unknown block {}", id))
+    }
+
+    fn stack(id: &str, count: &str) -> Result<ItemStack, String> {
+        let count = count.parse().ok().filter(|c| *c > 0).ok_or(format!("bad count {}", count))?;
+        Ok(ItemStack { block: Self::block(id)?, count })
+    }
+
+    // the recipe a filled in grid makes, cells are in rows from the top left
+    pub fn find(&self, grid: &[Option<BlockType>]) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| recipe.matches(grid))
+    }
+
+    // every way of making a block, for the recipe book
+    pub fn for_output(&self, block: BlockType) -> Vec<&Recipe> {
+        self.recipes.iter().filter(|recipe| recipe.output.block == block).collect()
+    }
+}
+
+// The cells items are put into while crafting, one block type per cell.
+pub struct CraftingGrid {
+    cells: [Option<ItemStack>; GRID_CELLS],
+}
+
+impl CraftingGrid {
+    pub fn new() -> CraftingGrid {
+        CraftingGrid {
+            cells: [None; GRID_CELLS],
+        }
+    }
+
+    pub fn cells(&self) -> &[Option<ItemStack>] {
+        &self.cells
+    }
+
+    // adds one block to a cell, false if the cell holds a different block
+    pub fn put(&mut self, cell: usize, block: BlockType) -> bool {
+        match self.cells.get_mut(cell) {
+            Some(Some(stack)) if stack.block == block => stack.count += 1,
+            Some(slot @ None) => *slot = Some(ItemStack { block, count: 1 }),
+            _ => return false,
+        }
+        true
+    }
+
+    fn pattern(&self) -> Vec<Option<BlockType>> {
+        self.cells.iter().map(|cell| cell.map(|stack| stack.block)).collect()
+    }
+
+    pub fn result<'a>(&self, recipes: &'a Recipes) -> Option<&'a Recipe> {
+        recipes.find(&self.pattern())
+    }
+
+    // uses up one of every ingredient, returning what was made
+    pub fn craft(&mut self, recipes: &Recipes) -> Option<ItemStack> {
+        let output = self.result(recipes)?.output;
+        for cell in self.cells.iter_mut() {
+            if let Some(stack) = cell {
+                stack.count -= 1;
+                if stack.count == 0 {
+                    *cell = None;
+                }
+            }
+        }
+        Some(output)
+    }
+
+    // empties the grid, returning everything that was in it
+    pub fn clear(&mut self) -> Vec<ItemStack> {
+        self.cells.iter_mut().filter_map(|cell| cell.take()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use BlockType::{DIRT, GRASS, SAND, STONE};
+
+    fn grid(cells: [Option<BlockType>; GRID_CELLS]) -> CraftingGrid {
+        let mut grid = CraftingGrid::new();
+        for (i, cell) in cells.iter().enumerate() {
+            if let Some(block) = cell {
+                grid.put(i, *block);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_shaped_recipes_match_anywhere_in_the_grid() {
+        let recipes = Recipes::builtin();
+        let top_left = grid([Some(SAND), Some(SAND), None, Some(SAND), Some(SAND), None, None, None, None]);
+        let bottom_right = grid([None, None, None, None, Some(SAND), Some(SAND), None, Some(SAND), Some(SAND)]);
+        let split = grid([Some(SAND), None, Some(SAND), Some(SAND), None, Some(SAND), None, None, None]);
+        assert_eq!(top_left.result(&recipes).unwrap().output, ItemStack { block: STONE, count: 1 });
+        assert_eq!(bottom_right.result(&recipes).unwrap().output, ItemStack { block: STONE, count: 1 });
+        assert!(split.result(&recipes).is_none());
+    }
+
+    #[test]
+    fn test_shapeless_recipes_ignore_position() {
+        let recipes = Recipes::builtin();
+        let grid = grid([None, None, Some(DIRT), None, None, None, Some(GRASS), None, None]);
+        assert_eq!(grid.result(&recipes).unwrap().output, ItemStack { block: GRASS, count: 2 });
+    }
+
+    #[test]
+    fn test_crafting_uses_one_of_each_ingredient() {
+        let recipes = Recipes::builtin();
+        let mut grid = grid([Some(STONE), None, None, None, None, None, None, None, None]);
+        grid.put(0, STONE);
+        assert!(!grid.put(0, SAND));
+
+        assert_eq!(grid.craft(&recipes), Some(ItemStack { block: SAND, count: 4 }));
+        assert_eq!(grid.cells()[0], Some(ItemStack { block: STONE, count: 1 }));
+        assert_eq!(grid.craft(&recipes), Some(ItemStack { block: SAND, count: 4 }));
+        assert_eq!(grid.craft(&recipes), None);
+        assert!(grid.clear().is_empty());
+    }
+
+    #[test]
+    fn test_lookup_by_output() {
+        let recipes = Recipes::parse("
+            shaped stone 1
+            row s.
+            row .s
+            key s sand
+            shapeless stone 3 sand sand dirt
+            shapeless dirt 1 grass
+        ").unwrap();
+        let stone = recipes.for_output(STONE);
+        assert_eq!(stone.len(), 2);
+        assert_eq!(stone[0].shape, Shape::Shaped { width: 2, cells: vec![Some(SAND), None, None, Some(SAND)] });
+        assert_eq!(stone[1].describe(), vec!["Stone x3", "Dirt + Sand + Sand"]);
+    }
+
+    #[test]
+    fn test_loads_the_data_file_or_falls_back() {
+        let file = std::env::temp_dir().join(format!("boxel-recipes-{}.txt", std::process::id()));
+        assert_eq!(Recipes::load(&file).unwrap().recipes.len(), Recipes::builtin().recipes.len());
+
+        fs::write(&file, "shapeless grass 1 dirt").unwrap();
+        let loaded = Recipes::load(&file);
+        fs::remove_file(&file).unwrap();
+        let recipes = loaded.unwrap();
+        assert_eq!(recipes.recipes.len(), 1);
+        assert_eq!(recipes.for_output(GRASS).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Recipes::parse("shapeless stone 1 lava").err().unwrap(), "line 1: unknown block lava");
+        assert_eq!(Recipes::parse("shaped stone 1\nrow ab\nkey a sand").err().unwrap(), "line 1: no key for b");
+        assert_eq!(Recipes::parse("key a sand").err().unwrap(), "line 1: key outside a shaped recipe");
+        assert!(Recipes::parse("shaped stone 0\nrow a\nkey a sand").is_err());
+        assert!(Recipes::parse("shaped stone 1\nrow aaaa\nkey a sand").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::engine::crafting::{GRID_CELLS, GRID_SIZE};
+use crate::engine::hotbar::Hotbar;
+use crate::primitives::CrosshairVertex;
+use glium::vertex::VertexBufferSlice;
+use glium::{Display, VertexBuffer};
+
+// screen space layout of the crafting grid, left of centre with the result box to its right
+pub const CELL_WIDTH: f32 = 0.1;
+pub const CELL_HEIGHT: f32 = 0.13;
+pub const LEFT: f32 = -0.45;
+pub const TOP: f32 = 0.45;
+pub const RESULT_LEFT: f32 = LEFT + (GRID_SIZE as f32 + 0.5) * CELL_WIDTH;
+// the recipe book is listed under the result box
+pub const BOOK_LEFT: f32 = RESULT_LEFT;
+pub const BOOK_TOP: f32 = TOP - 2.5 * CELL_HEIGHT;
+
+// Outline boxes for the crafting grid cells followed by the result box, drawn as a
+// LinesList with the crosshair program like the hotbar.
+pub struct CraftingPanel {
+    pub vbo: VertexBuffer<CrosshairVertex>,
+}
+
+impl CraftingPanel {
+    pub fn new(display: &Display) -> CraftingPanel {
+        let mut vertices: Vec<CrosshairVertex> = Vec::new();
+        for cell in 0..GRID_CELLS {
+            let (left, top) = Self::cell_corner(cell);
+            Hotbar::add_box(&mut vertices, left, top - CELL_HEIGHT, left + CELL_WIDTH, top);
+        }
+        Hotbar::add_box(&mut vertices, RESULT_LEFT, Self::result_top() - CELL_HEIGHT, RESULT_LEFT + CELL_WIDTH, Self::result_top());
+
+        let vbo = VertexBuffer::new(display, &vertices).expect("to create vb");
+        CraftingPanel {
+            vbo
+        }
+    }
+
+    // top left corner of a grid cell, cells go in rows from the top left
+    pub fn cell_corner(cell: usize) -> (f32, f32) {
+        let (column, row) = (cell % GRID_SIZE, cell / GRID_SIZE);
+        (LEFT + column as f32 * CELL_WIDTH, TOP - row as f32 * CELL_HEIGHT)
+    }
+
+    // level with the middle row of the grid
+    pub fn result_top() -> f32 {
+        TOP - (GRID_SIZE / 2) as f32 * CELL_HEIGHT
+    }
+
+    pub fn outlines(&self) -> VertexBufferSlice<'_, CrosshairVertex> {
+        self.vbo.slice(..).unwrap()
+    }
+}
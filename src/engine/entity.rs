@@ -0,0 +1,361 @@
+use crate::engine::block::BlockType;
+use crate::engine::chunk::ChunkCoordinate;
+use crate::engine::health::{Health, Resistances};
+use crate::engine::world::World;
+use crate::primitives::InstanceAttr;
+use glm::{vec3, Vec3};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(u64);
+
+// every entity has a transform, everything else is optional
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub position: Vec3, // centre of the entity
+    pub velocity: Vec3,
+}
+
+// falls, collides with the ground and slides to a stop
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Body {
+    pub size: f32, // edge length of the entity's cube
+    pub gravity: f32,
+    pub friction: f32, // fraction of horizontal speed lost per second on the ground
+    pub on_ground: bool,
+    pub impact: f32, // downward speed it landed with this tick, 0 when it didn't land
+}
+
+// drawn as a coloured cube through the instanced block program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Appearance {
+    pub color: (f32, f32, f32, f32),
+    pub scale: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Item {
+    pub block: BlockType,
+    pub count: u32,
+    pub age: f32, // seconds since dropped
+}
+
+// a creature, its rules and behaviours come from the definition named by kind
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mob {
+    pub kind: String,
+}
+
+// Component storage for everything in the world that isn't a block: one map per component
+// keyed by entity, plus an index of which chunk each entity is in so entities are saved and
+// unloaded together with their chunk. Owned by World, gameplay systems (items, mobs, ...)
+// work on the component maps directly.
+#[derive(Default)]
+pub struct Entities {
+    next_id: u64,
+    pub transforms: HashMap<EntityId, Transform>,
+    pub bodies: HashMap<EntityId, Body>,
+    pub appearances: HashMap<EntityId, Appearance>,
+    pub items: HashMap<EntityId, Item>,
+    pub mobs: HashMap<EntityId, Mob>,
+    pub healths: HashMap<EntityId, Health>,
+    by_chunk: HashMap<ChunkCoordinate, HashSet<EntityId>>,
+    chunk_of: HashMap<EntityId, ChunkCoordinate>,
+}
+
+impl Entities {
+    pub fn new() -> Entities {
+        Entities::default()
+    }
+
+    pub fn spawn(&mut self, transform: Transform) -> EntityId {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.transforms.insert(id, transform);
+        self.index(id);
+        id
+    }
+
+    pub fn despawn(&mut self, id: EntityId) {
+        self.transforms.remove(&id);
+        self.bodies.remove(&id);
+        self.appearances.remove(&id);
+        self.items.remove(&id);
+        self.mobs.remove(&id);
+        self.healths.remove(&id);
+        if let Some(chunk) = self.chunk_of.remove(&id) {
+            if let Some(ids) = self.by_chunk.get_mut(&chunk) {
+                ids.remove(&id);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    // sorted so systems visit entities in the same order every run
+    pub fn ids(&self) -> Vec<EntityId> {
+        let mut ids: Vec<EntityId> = self.transforms.keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn in_chunk(&self, chunk: &ChunkCoordinate) -> Vec<EntityId> {
+        let mut ids: Vec<EntityId> = self.by_chunk.get(chunk).map_or(Vec::new(), |ids| ids.iter().copied().collect());
+        ids.sort();
+        ids
+    }
+
+    fn index(&mut self, id: EntityId) {
+        let position = match self.transforms.get(&id) {
+            Some(transform) => transform.position,
+            None => return,
+        };
+        let chunk = World::locate(&World::block_at(&position)).0;
+        if let Some(old) = self.chunk_of.insert(id, chunk) {
+            if old == chunk {
+                return;
+            }
+            if let Some(ids) = self.by_chunk.get_mut(&old) {
+                ids.remove(&id);
+            }
+        }
+        self.by_chunk.entry(chunk).or_insert_with(HashSet::new).insert(id);
+    }
+
+    // update hook, run once per simulation tick
+    pub fn update(&mut self, world: &World, dt: f32) {
+        for id in self.ids() {
+            if let (Some(transform), Some(body)) = (self.transforms.get_mut(&id), self.bodies.get_mut(&id)) {
+                Self::simulate(transform, body, world, dt);
+            }
+            self.index(id);
+        }
+    }
+
+    fn simulate(transform: &mut Transform, body: &mut Body, world: &World, dt: f32) {
+        // nothing moves while the chunk it is in is missing
+        if !world.is_loaded(&World::block_at(&transform.position)) {
+            return;
+        }
+
+        body.impact = 0.;
+        transform.velocity.y -= body.gravity * dt;
+        if body.on_ground {
+            let friction = (1. - body.friction * dt).max(0.);
+            transform.velocity.x *= friction;
+            transform.velocity.z *= friction;
+        }
+
+        let position = transform.position;
+        let next = position + transform.velocity * dt;
+        if !world.is_solid(&World::block_at(&vec3(next.x, position.y, next.z))) {
+            transform.position.x = next.x;
+            transform.position.z = next.z;
+        } else {
+            transform.velocity.x = 0.;
+            transform.velocity.z = 0.;
+        }
+
+        let below = World::block_at(&vec3(transform.position.x, next.y - body.size / 2., transform.position.z));
+        if transform.velocity.y <= 0. && world.is_solid(&below) {
+            // rest on top of the block that was hit
+            if !body.on_ground {
+                body.impact = -transform.velocity.y;
+            }
+            transform.position.y = below.y as f32 + 0.5 + body.size / 2.;
+            transform.velocity.y = 0.;
+            body.on_ground = true;
+        } else {
+            transform.position.y = next.y;
+            body.on_ground = false;
+        }
+    }
+
+    // render hook, one cube instance per visible entity, fully lit until the world shades it
+    pub fn instances(&self) -> Vec<InstanceAttr> {
+        self.appearances
+            .iter()
+            .filter_map(|(id, appearance)| {
+                self.transforms.get(id).map(|t| {
+                    let position = (t.position.x, t.position.y, t.position.z);
+                    InstanceAttr::new(position, appearance.color, appearance.scale, (1., 1., 1.), (1., 1., 1.))
+                })
+            })
+            .collect()
+    }
+
+    // removes every entity in the chunk, returning them as save file lines
+    pub fn unload_chunk(&mut self, chunk: &ChunkCoordinate) -> String {
+        let mut data = String::new();
+        for id in self.in_chunk(chunk) {
+            data.push_str(&self.save_entity(id));
+            data.push('\n');
+            self.despawn(id);
+        }
+        self.by_chunk.remove(chunk);
+        data
+    }
+
+    pub fn save_chunk(&self, chunk: &ChunkCoordinate) -> String {
+        let mut data = String::new();
+        for id in self.in_chunk(chunk) {
+            data.push_str(&self.save_entity(id));
+            data.push('\n');
+        }
+        data
+    }
+
+    // "entity" followed by one keyword and its fields per component
+    fn save_entity(&self, id: EntityId) -> String {
+        let mut line = String::from("entity");
+        if let Some(t) = self.transforms.get(&id) {
+            line.push_str(&format!(" transform {} {} {} {} {} {}",
+                t.position.x, t.position.y, t.position.z, t.velocity.x, t.velocity.y, t.velocity.z));
+        }
+        if let Some(b) = self.bodies.get(&id) {
+            line.push_str(&format!(" body {} {} {}", b.size, b.gravity, b.friction));
+        }
+        if let Some(a) = self.appearances.get(&id) {
+            line.push_str(&format!(" appearance {} {} {} {} {}", a.color.0, a.color.1, a.color.2, a.color.3, a.scale));
+        }
+        if let Some(i) = self.items.get(&id) {
+            line.push_str(&format!(" item {} {} {}", i.block.id(), i.count, i.age));
+        }
+        if let Some(m) = self.mobs.get(&id) {
+            line.push_str(&format!(" mob {}", m.kind));
+        }
+        if let Some(h) = self.healths.get(&id) {
+            let r = h.resistances;
+            line.push_str(&format!(" health {} {} {} {} {} {}", h.current, h.max, h.air, r.fall, r.drowning, r.attack));
+        }
+        line
+    }
+
+    // respawns the entities from a chunk's save data, other lines and entities that can't be
+    // read are skipped
+    pub fn load_chunk(&mut self, data: &str) {
+        for line in data.lines().filter(|l| l.starts_with("entity ")) {
+            self.load_entity(line);
+        }
+    }
+
+    fn load_entity(&mut self, line: &str) -> Option<EntityId> {
+        let tokens: Vec<&str> = line.split_whitespace().skip(1).collect();
+        let floats = |at: usize, n: usize| -> Option<Vec<f32>> {
+            tokens.get(at..at + n)?.iter().map(|t| t.parse().ok()).collect()
+        };
+
+        let mut transform = None;
+        let mut body = None;
+        let mut appearance = None;
+        let mut item = None;
+        let mut mob = None;
+        let mut health = None;
+        let mut i = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                "transform" => {
+                    let f = floats(i + 1, 6)?;
+                    transform = Some(Transform { position: vec3(f[0], f[1], f[2]), velocity: vec3(f[3], f[4], f[5]) });
+                    i += 7;
+                }
+                "body" => {
+                    let f = floats(i + 1, 3)?;
+                    body = Some(Body { size: f[0], gravity: f[1], friction: f[2], on_ground: false, impact: 0. });
+                    i += 4;
+                }
+                "appearance" => {
+                    let f = floats(i + 1, 5)?;
+                    appearance = Some(Appearance { color: (f[0], f[1], f[2], f[3]), scale: f[4] });
+                    i += 6;
+                }
+                "item" => {
+                    let block = BlockType::from_id(tokens.get(i + 1)?)?;
+                    let count = tokens.get(i + 2)?.parse().ok()?;
+                    let age = tokens.get(i + 3)?.parse().ok()?;
+                    item = Some(Item { block, count, age });
+                    i += 4;
+                }
+                "mob" => {
+                    mob = Some(Mob { kind: tokens.get(i + 1)?.to_string() });
+                    i += 2;
+                }
+                "health" => {
+                    let f = floats(i + 1, 6)?;
+                    let resistances = Resistances { fall: f[3], drowning: f[4], attack: f[5] };
+                    health = Some(Health { current: f[0], max: f[1], air: f[2], resistances });
+                    i += 7;
+                }
+                _ => return None,
+            }
+        }
+
+        let id = self.spawn(transform?);
+        if let Some(body) = body {
+            self.bodies.insert(id, body);
+        }
+        if let Some(appearance) = appearance {
+            self.appearances.insert(id, appearance);
+        }
+        if let Some(item) = item {
+            self.items.insert(id, item);
+        }
+        if let Some(mob) = mob {
+            self.mobs.insert(id, mob);
+        }
+        if let Some(health) = health {
+            self.healths.insert(id, health);
+        }
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point2;
+
+    fn item_at(entities: &mut Entities, position: Vec3) -> EntityId {
+        let id = entities.spawn(Transform { position, velocity: vec3(0., 1., 0.) });
+        entities.bodies.insert(id, Body { size: 0.25, gravity: 20., friction: 8., on_ground: false, impact: 0. });
+        entities.appearances.insert(id, Appearance { color: (1., 0., 0., 1.), scale: 0.25 });
+        entities.items.insert(id, Item { block: BlockType::SAND, count: 3, age: 1.5 });
+        id
+    }
+
+    #[test]
+    fn test_spatial_index_follows_chunks() {
+        let mut entities = Entities::new();
+        let id = item_at(&mut entities, vec3(4., 20., 4.));
+        assert_eq!(entities.in_chunk(&Point2::new(0, 0)), vec![id]);
+
+        entities.transforms.get_mut(&id).unwrap().position = vec3(-4., 20., 20.);
+        entities.index(id);
+        assert!(entities.in_chunk(&Point2::new(0, 0)).is_empty());
+        assert_eq!(entities.in_chunk(&Point2::new(-1, 1)), vec![id]);
+    }
+
+    #[test]
+    fn test_unload_and_reload_chunk() {
+        let mut entities = Entities::new();
+        let id = item_at(&mut entities, vec3(4., 20., 4.));
+        let other = item_at(&mut entities, vec3(40., 20., 4.));
+        let saved = (entities.transforms[&id], entities.bodies[&id], entities.items[&id]);
+
+        let data = entities.unload_chunk(&Point2::new(0, 0));
+        assert_eq!(entities.ids(), vec![other]);
+
+        entities.load_chunk(&data);
+        let loaded = entities.in_chunk(&Point2::new(0, 0))[0];
+        assert_eq!(entities.transforms[&loaded], saved.0);
+        assert_eq!(entities.bodies[&loaded], saved.1);
+        assert_eq!(entities.items[&loaded], saved.2);
+        assert!(entities.appearances.contains_key(&loaded));
+    }
+}
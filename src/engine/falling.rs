@@ -0,0 +1,105 @@
+use crate::engine::world::World;
+use glm::{vec3, IVec3};
+
+// ticks a falling block waits before each drop, 30 blocks a second
+pub const FALL_DELAY: u64 = 2;
+// nothing is generated below this, a block falling past it is gone
+const WORLD_BOTTOM: i32 = 0;
+
+// Moves a block that falls when unsupported down a cell, replacing any liquid it sinks
+// into. Both changes notify their neighbours, so the block keeps falling until it lands
+// and whatever was resting on it follows it down.
+pub fn fall_tick(world: &mut World, block: &IVec3) {
+    let block_type = match world.get_block(block) {
+        Some(b) if b.falls() => *b,
+        _ => return,
+    };
+    let below = block - vec3(0, 1, 0);
+    if world.is_solid(&below) {
+        return;
+    }
+    if below.y >= WORLD_BOTTOM {
+        world.set_block(&below, block_type);
+    }
+    world.remove_block(block);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::block::BlockType;
+
+    fn run(world: &mut World, seconds: f32) {
+        for _ in 0..(seconds * 60.) as usize {
+            world.tick(1. / 60.);
+        }
+    }
+
+    fn column(world: &World, x: i32, z: i32) -> Vec<Option<BlockType>> {
+        (16..22).map(|y| world.get_block(&vec3(x, y, z)).copied()).collect()
+    }
+
+    #[test]
+    fn test_stack_lands_on_the_first_solid_block() {
+        let mut world = World::flat(0, true);
+        world.set_block(&vec3(5, 17, 5), BlockType::STONE);
+        world.set_block(&vec3(5, 18, 5), BlockType::STONE);
+        for y in 19..22 {
+            world.set_block(&vec3(5, y, 5), BlockType::SAND);
+        }
+        run(&mut world, 1.);
+        assert_eq!(world.get_block(&vec3(5, 21, 5)), Some(&BlockType::SAND), "supported sand stays put");
+
+        world.remove_block(&vec3(5, 17, 5));
+        world.remove_block(&vec3(5, 18, 5));
+        run(&mut world, 1.);
+        let sand = Some(BlockType::SAND);
+        assert_eq!(column(&world, 5, 5), vec![Some(BlockType::STONE), sand, sand, sand, None, None]);
+    }
+
+    #[test]
+    fn test_sinks_through_water() {
+        let mut world = World::flat(0, true);
+        for x in 0..3 {
+            world.set_block(&vec3(x, 17, 0), BlockType::STONE);
+        }
+        world.set_block(&vec3(1, 17, 0), BlockType::WATER);
+        world.set_block(&vec3(1, 17, 1), BlockType::STONE);
+        world.set_block(&vec3(1, 19, 0), BlockType::SAND);
+        run(&mut world, 1.);
+        assert_eq!(world.get_block(&vec3(1, 17, 0)), Some(&BlockType::SAND));
+        assert_eq!(world.get_block(&vec3(1, 18, 0)), None);
+    }
+
+    #[test]
+    fn test_falls_out_of_the_world() {
+        let mut world = World::flat(0, false);
+        let floor = vec3(1, 16, 0);
+        assert_eq!(world.get_block(&floor), Some(&BlockType::SAND));
+        run(&mut world, 1.);
+        assert_eq!(world.get_block(&floor), Some(&BlockType::SAND), "untouched blocks don't fall");
+
+        // anything next to it changing wakes it up
+        world.remove_block(&vec3(2, 16, 0));
+        run(&mut world, 2.);
+        assert_eq!(world.get_block(&floor), None);
+        assert!((0..17).all(|y| world.get_block(&vec3(1, y, 0)).is_none()));
+    }
+
+    #[test]
+    fn test_cascades_on_both_sides_of_a_chunk_border() {
+        let mut world = World::flat(1, true);
+        for x in -1..=0 {
+            world.set_block(&vec3(x, 17, 3), BlockType::STONE);
+            world.set_block(&vec3(x, 18, 3), BlockType::SAND);
+            world.set_block(&vec3(x, 19, 3), BlockType::SAND);
+        }
+        world.remove_block(&vec3(-1, 17, 3));
+        world.remove_block(&vec3(0, 17, 3));
+        run(&mut world, 1.);
+
+        let sand = Some(BlockType::SAND);
+        assert_eq!(column(&world, -1, 3)[1..], [sand, sand, None, None, None]);
+        assert_eq!(column(&world, 0, 3)[1..], [sand, sand, None, None, None]);
+    }
+}
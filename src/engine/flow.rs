@@ -0,0 +1,177 @@
+use crate::engine::block::BlockType;
+use crate::engine::world::World;
+use glm::{vec3, IVec3};
+
+// still water and anything it keeps topped up, each block flowed sideways costs a level
+pub const SOURCE_LEVEL: u8 = 8;
+// water pouring down a column stays this full, so it spreads out again where it lands
+const FALLING_LEVEL: u8 = SOURCE_LEVEL - 1;
+// ticks between a change and the water next to it reacting, 12 steps a second
+pub const FLOW_DELAY: u64 = 5;
+
+const SIDES: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+// Cellular water. Each update a flowing block settles to the level its neighbours feed it,
+// dries up once nothing does, then pours down or, when it rests on something, out to the
+// sides. Every block it changes wakes the neighbours through the world's change
+// notification, so a disturbance ripples outwards a step at a time until it settles.
+pub fn water_tick(world: &mut World, block: &IVec3) {
+    let block_type = match world.get_block(block) {
+        Some(b) if b.is_liquid() => *b,
+        _ => return,
+    };
+    let mut level = world.liquid_level(block);
+    if level < SOURCE_LEVEL {
+        level = fed_level(world, block, block_type);
+        if level == 0 {
+            world.remove_block(block);
+            return;
+        }
+        world.set_liquid(block, block_type, level);
+    }
+
+    let below = block - vec3(0, 1, 0);
+    if can_flow(world, &below, block_type, FALLING_LEVEL) {
+        world.set_liquid(&below, block_type, FALLING_LEVEL);
+        return;
+    }
+    // water pouring into more water just joins it
+    let resting = world.liquid_level(&below) == SOURCE_LEVEL || !world.get_block(&below).map_or(false, |b| b.is_liquid());
+    if !resting || level <= 1 {
+        return;
+    }
+    for (x, z) in SIDES.iter() {
+        let side = block + vec3(*x, 0, *z);
+        if can_flow(world, &side, block_type, level - 1) {
+            world.set_liquid(&side, block_type, level - 1);
+        }
+    }
+}
+
+// what the neighbours can supply: a full column from above, a new source between two
+// sources on solid ground, otherwise a level less than the fullest side
+fn fed_level(world: &World, block: &IVec3, block_type: BlockType) -> u8 {
+    if world.get_block(&(block + vec3(0, 1, 0))) == Some(&block_type) {
+        return FALLING_LEVEL;
+    }
+    let sides: Vec<u8> = SIDES
+        .iter()
+        .map(|(x, z)| block + vec3(*x, 0, *z))
+        .filter(|side| world.get_block(side) == Some(&block_type))
+        .map(|side| world.liquid_level(&side))
+        .collect();
+
+    let below = block - vec3(0, 1, 0);
+    let supported = world.liquid_level(&below) == SOURCE_LEVEL
+        || world.get_block(&below).map_or(!world.is_loaded(&below), |b| b.is_solid());
+    if supported && sides.iter().filter(|l| **l == SOURCE_LEVEL).count() >= 2 {
+        return SOURCE_LEVEL;
+    }
+    sides.into_iter().max().unwrap_or(0).saturating_sub(1)
+}
+
+// empty space, or the same liquid at a lower level, in a loaded chunk
+fn can_flow(world: &World, block: &IVec3, block_type: BlockType, level: u8) -> bool {
+    if !world.is_loaded(block) {
+        return false;
+    }
+    match world.get_block(block) {
+        None => true,
+        Some(b) if *b == block_type => world.liquid_level(block) < level,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(world: &mut World, seconds: f32) {
+        for _ in 0..(seconds * 60.) as usize {
+            world.tick(1. / 60.);
+        }
+    }
+
+    #[test]
+    fn test_spreads_a_level_per_block() {
+        let mut world = World::flat(0, true);
+        world.set_block(&vec3(8, 17, 8), BlockType::WATER);
+        run(&mut world, 5.);
+
+        for distance in 0..8 {
+            assert_eq!(world.liquid_level(&vec3(8 + distance, 17, 8)), SOURCE_LEVEL - distance as u8);
+            assert_eq!(world.liquid_level(&vec3(8, 17, 8 - distance)), SOURCE_LEVEL - distance as u8);
+        }
+        assert_eq!(world.liquid_level(&vec3(4, 17, 5)), SOURCE_LEVEL - 7);
+        assert_eq!(world.get_block(&vec3(0, 17, 8)), None);
+        assert_eq!(world.get_block(&vec3(8, 18, 8)), None);
+    }
+
+    #[test]
+    fn test_falls_then_spreads() {
+        let mut world = World::flat(0, true);
+        world.set_block(&vec3(8, 21, 8), BlockType::WATER);
+        run(&mut world, 5.);
+
+        for y in 17..21 {
+            assert_eq!(world.liquid_level(&vec3(8, y, 8)), FALLING_LEVEL);
+        }
+        assert_eq!(world.get_block(&vec3(9, 20, 8)), None, "falling water doesn't spread mid-air");
+        assert_eq!(world.liquid_level(&vec3(9, 17, 8)), FALLING_LEVEL - 1);
+    }
+
+    #[test]
+    fn test_drains_without_a_source() {
+        let mut world = World::flat(0, true);
+        let source = vec3(8, 17, 8);
+        world.set_block(&source, BlockType::WATER);
+        run(&mut world, 5.);
+        let revision = world.revision();
+
+        world.remove_block(&source);
+        run(&mut world, 5.);
+        let wet = (0..16).flat_map(|x| (0..16).map(move |z| vec3(x, 17, z))).filter(|b| world.get_block(b).is_some());
+        assert_eq!(wet.count(), 0);
+
+        run(&mut world, 1.);
+        let settled = world.revision();
+        run(&mut world, 1.);
+        assert!(settled > revision);
+        assert_eq!(world.revision(), settled, "nothing changes once drained");
+    }
+
+    #[test]
+    fn test_two_sources_make_a_third() {
+        let mut world = World::flat(0, true);
+        world.set_block(&vec3(4, 17, 4), BlockType::WATER);
+        world.set_block(&vec3(6, 17, 4), BlockType::WATER);
+        run(&mut world, 1.);
+        assert_eq!(world.liquid_level(&vec3(5, 17, 4)), SOURCE_LEVEL);
+
+        world.remove_block(&vec3(4, 17, 4));
+        world.remove_block(&vec3(6, 17, 4));
+        run(&mut world, 3.);
+        assert_eq!(world.liquid_level(&vec3(5, 17, 4)), SOURCE_LEVEL);
+        assert_eq!(world.liquid_level(&vec3(4, 17, 4)), SOURCE_LEVEL - 1);
+    }
+
+    #[test]
+    fn test_fills_a_hole_next_to_a_lake() {
+        let mut world = World::flat(0, true);
+        // a pond along the chunk's edge, walled in where the chunk doesn't already stop it
+        for x in 0..4 {
+            world.set_block(&vec3(x, 17, 0), BlockType::WATER);
+        }
+        for x in 0..5 {
+            world.set_block(&vec3(x, 17, 1), BlockType::STONE);
+        }
+        world.set_block(&vec3(4, 17, 0), BlockType::STONE);
+        run(&mut world, 1.);
+        assert_eq!(world.get_block(&vec3(5, 17, 0)), None, "the wall holds still water back");
+
+        world.remove_block(&vec3(4, 17, 0));
+        run(&mut world, 1.);
+        assert_eq!(world.liquid_level(&vec3(4, 17, 0)), SOURCE_LEVEL - 1);
+        assert_eq!(world.liquid_level(&vec3(5, 17, 0)), SOURCE_LEVEL - 2);
+    }
+}
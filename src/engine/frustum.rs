@@ -0,0 +1,119 @@
+use glm::{Mat4, Vec3, Vec4};
+
+// Chunk sections drawn and skipped in the last frame, for the debug overlay.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CullStats {
+    pub drawn: usize,
+    pub culled: usize, // outside the frustum
+    pub hidden: usize, // in the frustum, but with no open path to them from the camera
+}
+
+// The six planes bounding what a camera can see, pointing inwards. Each is (a, b, c, d)
+// with a point p inside when a*p.x + b*p.y + c*p.z + d >= 0.
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    // Pulls the planes out of a projection * view matrix (Gribb and Hartmann, "Fast
+    // Extraction of Viewing Frustum Planes from the World-View-Projection Matrix"). Clip space
+    // keeps -w <= x, y, z <= w, so each plane is the last row plus or minus another.
+    pub fn from_matrix(matrix: &Mat4) -> Frustum {
+        let row = |i: usize| -> Vec4 { matrix.row(i).transpose() };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, w + z, w - z];
+        Frustum {
+            planes: [
+                normalize(&planes[0]),
+                normalize(&planes[1]),
+                normalize(&planes[2]),
+                normalize(&planes[3]),
+                normalize(&planes[4]),
+                normalize(&planes[5]),
+            ],
+        }
+    }
+
+    // Conservative: false only when the box is entirely outside one of the planes, so a box
+    // near a corner of the frustum can still pass while being out of view.
+    pub fn intersects_box(&self, min: &Vec3, max: &Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane's normal
+            let corner = Vec3::new(
+                if plane.x >= 0. { max.x } else { min.x },
+                if plane.y >= 0. { max.y } else { min.y },
+                if plane.z >= 0. { max.z } else { min.z },
+            );
+            distance(plane, &corner) >= 0.
+        })
+    }
+}
+
+fn distance(plane: &Vec4, point: &Vec3) -> f32 {
+    plane.x * point.x + plane.y * point.y + plane.z * point.z + plane.w
+}
+
+// scales the plane so distances come out in world units
+fn normalize(plane: &Vec4) -> Vec4 {
+    plane / plane.xyz().norm()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::vec3;
+
+    // the same projection and view CameraState builds, which sees along the opposite of the
+    // direction handed to look_at
+    fn camera(eye: Vec3, forward: Vec3) -> Frustum {
+        let perspective = glm::perspective_lh(1024. / 768., std::f32::consts::FRAC_PI_2, 1., 1024.);
+        let view = glm::look_at(&eye, &(eye - forward), &vec3(0., 1., 0.));
+        Frustum::from_matrix(&(perspective * view))
+    }
+
+    fn contains_point(frustum: &Frustum, point: Vec3) -> bool {
+        frustum.intersects_box(&point, &point)
+    }
+
+    fn unit_box(frustum: &Frustum, centre: Vec3) -> bool {
+        frustum.intersects_box(&centre.add_scalar(-0.5), &centre.add_scalar(0.5))
+    }
+
+    #[test]
+    fn test_points_in_front_are_inside() {
+        let frustum = camera(vec3(0., 0., 0.), vec3(0., 0., -1.));
+        assert!(contains_point(&frustum, vec3(0., 0., -10.)));
+        assert!(!contains_point(&frustum, vec3(0., 0., 10.)), "behind");
+        assert!(!contains_point(&frustum, vec3(0., 0., -0.5)), "closer than the near plane");
+        assert!(!contains_point(&frustum, vec3(0., 0., -2000.)), "past the far plane");
+        assert!(!contains_point(&frustum, vec3(0., 20., -10.)), "above");
+        assert!(!contains_point(&frustum, vec3(30., 0., -10.)), "off to the side");
+    }
+
+    #[test]
+    fn test_boxes_behind_or_beside_are_culled() {
+        let frustum = camera(vec3(0., 0., 0.), vec3(0., 0., -1.));
+        assert!(unit_box(&frustum, vec3(0., 0., -10.)));
+        assert!(!unit_box(&frustum, vec3(0., 0., 10.)));
+        assert!(!unit_box(&frustum, vec3(-30., 0., -10.)));
+        assert!(!unit_box(&frustum, vec3(0., -20., -10.)));
+    }
+
+    #[test]
+    fn test_boxes_across_a_plane_are_kept() {
+        let frustum = camera(vec3(0., 0., 0.), vec3(0., 0., -1.));
+        // the camera stands inside this one
+        assert!(frustum.intersects_box(&vec3(-8., -8., -8.), &vec3(8., 8., 8.)));
+        // 10 blocks out the view is 20 high, 11 blocks out 22
+        assert!(frustum.intersects_box(&vec3(-1., 9., -11.), &vec3(1., 20., -9.)));
+        assert!(!frustum.intersects_box(&vec3(-1., 11.5, -11.), &vec3(1., 20., -9.)));
+    }
+
+    #[test]
+    fn test_follows_the_camera() {
+        let frustum = camera(vec3(100., 20., 50.), vec3(1., 0., 0.));
+        assert!(unit_box(&frustum, vec3(130., 20., 50.)));
+        assert!(!unit_box(&frustum, vec3(70., 20., 50.)));
+        assert!(!unit_box(&frustum, vec3(100., 20., 80.)));
+    }
+}
@@ -1,20 +1,74 @@
 use crate::engine::block::BlockType;
 use crate::engine::chunk::{Chunk, ChunkCoordinate};
 use crate::config::HEIGHT_OFFSET;
+use crate::engine::seed::{Seed, Subsystem};
 use rand;
 
 use glm::{vec2};
+use image::error::{ImageError, ImageResult, ParameterError, ParameterErrorKind};
+use image::{GenericImageView, GrayImage, RgbImage};
 use noise::{Perlin, NoiseFn, Seedable};
+use std::path::Path;
+
+// local height water is filled up to over low terrain
+pub const SEA_LEVEL: i32 = -1;
 
 pub trait WorldGenerator {
     fn generate(&self, coordinate: ChunkCoordinate) -> Chunk;
+
+    // world space height of the top solid block of a column, without generating its chunk
+    fn surface_height(&self, x: i32, z: i32) -> i32;
+
+    fn is_underwater(&self, x: i32, z: i32) -> bool {
+        self.surface_height(x, z) < SEA_LEVEL + HEIGHT_OFFSET
+    }
+
+    // generators without climate are all plains
+    fn climate(&self) -> Option<Climate> {
+        None
+    }
 }
 
-pub struct PlanarGenerator {}
+// Climate zones. They don't shape the terrain yet, only what lives on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Plains,
+    Forest,
+    Desert,
+    Tundra,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 4] = [Biome::Plains, Biome::Forest, Biome::Desert, Biome::Tundra];
+
+    // stable identifier used in data files
+    pub fn id(&self) -> &'static str {
+        match *self {
+            Biome::Plains => "plains",
+            Biome::Forest => "forest",
+            Biome::Desert => "desert",
+            Biome::Tundra => "tundra",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Biome> {
+        Biome::ALL.iter().find(|b| b.id() == id).copied()
+    }
+}
+
+// A flat floor one block thick at world height 16, its surface cycling dirt, sand, stone and
+// grass along x + z.
+pub struct PlanarGenerator {
+    supported: bool, // a layer of dirt under the floor, so its sand doesn't fall
+}
 
 impl PlanarGenerator {
     pub fn new() -> PlanarGenerator {
-        PlanarGenerator {}
+        PlanarGenerator { supported: false }
+    }
+
+    pub fn supported() -> PlanarGenerator {
+        PlanarGenerator { supported: true }
     }
 }
 
@@ -29,27 +83,73 @@ impl WorldGenerator for PlanarGenerator {
                     2 => BlockType::STONE,
                     3..=std::i32::MAX => BlockType::GRASS,
                 };
-                // chunk.add_block(glm::vec3(x as f32, 0., z as f32), block_type);
+                chunk.add_block([x, 0, z].into(), block_type);
+                if self.supported {
+                    chunk.add_block([x, -1, z].into(), BlockType::DIRT);
+                }
             }
         }
         chunk
     }
+
+    fn surface_height(&self, _x: i32, _z: i32) -> i32 {
+        HEIGHT_OFFSET
+    }
+}
+
+// Temperature and humidity. Kept apart from the terrain noise so the world can look up a
+// column's biome without waiting on the generator, which is busy on other threads.
+#[derive(Clone, Copy)]
+pub struct Climate {
+    noise: Perlin,
+}
+
+impl Climate {
+    pub fn new(seed: &Seed) -> Climate {
+        Climate {
+            noise: Perlin::new().set_seed(seed.derive_u32(Subsystem::Biomes)),
+        }
+    }
+
+    pub fn biome(&self, x: i32, z: i32) -> Biome {
+        // temperature and humidity are two far apart samples of the same low frequency noise
+        let (x, z) = (x as f64 * 0.004, z as f64 * 0.004);
+        let temperature = self.noise.get([x, z]);
+        let humidity = self.noise.get([x + 500., z + 500.]);
+        if temperature > 0.3 {
+            Biome::Desert
+        } else if temperature < -0.3 {
+            Biome::Tundra
+        } else if humidity > 0.1 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
 }
 
 pub struct PerlinGenerator {
-    perlin: Perlin
+    perlin: Perlin,
+    climate: Climate,
 }
 
 impl PerlinGenerator {
-    pub fn new(seed: u32) -> PerlinGenerator {
+    pub fn new(seed: &Seed) -> PerlinGenerator {
         let mut perlin = Perlin::new();
-        perlin = perlin.set_seed(seed);
+        perlin = perlin.set_seed(seed.derive_u32(Subsystem::Terrain));
         PerlinGenerator {
-            perlin
+            perlin,
+            climate: Climate::new(seed),
         }
     }
 
-    fn get_block(&self, y: i32) -> BlockType {
+    // local height of the surface block
+    fn height(&self, x: i32, z: i32) -> i32 {
+        let block_world: [f64; 2] = (vec2(x as f64, z as f64) * 0.015).into();
+        (self.perlin.get(block_world).powi(3) * 16. as f64) as i32
+    }
+
+    fn get_block(y: i32) -> BlockType {
         match y {
             std::i32::MIN..=0 => BlockType::SAND,
             1..=12 => BlockType::GRASS,
@@ -61,29 +161,219 @@ impl PerlinGenerator {
 impl WorldGenerator for PerlinGenerator {
     fn generate(&self, coordinate: ChunkCoordinate) -> Chunk {
         let mut chunk = Chunk::new(coordinate);
-        let chunk_world: glm::TVec2<f64> = vec2(chunk.world_origin()[0] as f64, chunk.world_origin()[2] as f64);
+        let origin = chunk.world_origin();
         for x in 0..16 {
             for z in 0..16 {
-                let block_world: [f64; 2] = ((chunk_world + vec2(x as f64, z as f64)) * 0.015).into();
-                let y: i32 = (self.perlin.get(block_world).powi(3) * 16. as f64) as i32;
+                let y = self.height(origin[0] + x, origin[2] + z);
                 // println!("pos: {:#?} y: {:?}", block_world, y);
-                chunk.add_block([x, y, z].into(), self.get_block(y));
-                
-                let mut i = 1;
-                for _ in y..-1 {
-                    // println!("Adding water");
-                    chunk.add_block([x, (y + i), z].into(), BlockType::WATER);
-                    i += 1;
-                }
+                fill_column(&mut chunk, x, z, y, Self::get_block(y));
+            }
+        }
+        chunk
+    }
 
-                let min_y = -HEIGHT_OFFSET;
-                i = 1;
-                for _ in min_y..y - 1 {
-                    chunk.add_block([x, (y - i), z].into(), BlockType::STONE);
-                    i += 1;
-                }
+    fn surface_height(&self, x: i32, z: i32) -> i32 {
+        self.height(x, z) + HEIGHT_OFFSET
+    }
+
+    fn climate(&self) -> Option<Climate> {
+        Some(self.climate)
+    }
+}
+
+// surface block at y, water up to sea level and stone down to the bottom of the world
+fn fill_column(chunk: &mut Chunk, x: i32, z: i32, y: i32, surface: BlockType) {
+    chunk.add_block([x, y, z].into(), surface);
+
+    let mut i = 1;
+    for _ in y..SEA_LEVEL {
+        // println!("Adding water");
+        chunk.add_block([x, (y + i), z].into(), BlockType::WATER);
+        i += 1;
+    }
+
+    let min_y = -HEIGHT_OFFSET;
+    i = 1;
+    for _ in min_y..y - 1 {
+        chunk.add_block([x, (y - i), z].into(), BlockType::STONE);
+        i += 1;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeMode {
+    Tile,
+    Clamp,
+}
+
+// world heights black and white are scaled to unless the heightmap is given its own range
+pub const DEFAULT_HEIGHT_RANGE: (i32, i32) = (2, HEIGHT_OFFSET + 16);
+
+// Builds terrain from a grayscale heightmap, one pixel per block column.
+// Pixel (0, 0) sits at world (0, 0); black maps to min_height and white to max_height.
+pub struct HeightmapGenerator {
+    heights: GrayImage,
+    materials: Option<RgbImage>,
+    edges: EdgeMode,
+    min_height: i32, // local, like the heights generate works in
+    max_height: i32,
+}
+
+impl HeightmapGenerator {
+    pub fn new(heights: GrayImage, materials: Option<RgbImage>, edges: EdgeMode) -> HeightmapGenerator {
+        HeightmapGenerator {
+            heights,
+            materials,
+            edges,
+            min_height: DEFAULT_HEIGHT_RANGE.0 - HEIGHT_OFFSET,
+            max_height: DEFAULT_HEIGHT_RANGE.1 - HEIGHT_OFFSET,
+        }
+    }
+
+    // the world heights black and white map to
+    pub fn set_height_range(&mut self, min: i32, max: i32) {
+        self.min_height = min - HEIGHT_OFFSET;
+        self.max_height = max - HEIGHT_OFFSET;
+    }
+
+    pub fn open<P: AsRef<Path>>(heightmap: P, materials: Option<P>, edges: EdgeMode) -> ImageResult<HeightmapGenerator> {
+        let heights = non_empty(image::open(heightmap)?.to_luma())?;
+        let materials = match materials {
+            Some(path) => Some(non_empty(image::open(path)?.to_rgb())?),
+            None => None,
+        };
+        Ok(HeightmapGenerator::new(heights, materials, edges))
+    }
+
+    fn pixel(&self, x: i32, z: i32, width: u32, height: u32) -> (u32, u32) {
+        match self.edges {
+            EdgeMode::Tile => (x.rem_euclid(width as i32) as u32, z.rem_euclid(height as i32) as u32),
+            EdgeMode::Clamp => (x.max(0).min(width as i32 - 1) as u32, z.max(0).min(height as i32 - 1) as u32),
+        }
+    }
+
+    pub fn height(&self, x: i32, z: i32) -> i32 {
+        let (px, pz) = self.pixel(x, z, self.heights.width(), self.heights.height());
+        let luma = self.heights.get_pixel(px, pz)[0] as f32 / 255.;
+        self.min_height + (luma * (self.max_height - self.min_height) as f32).round() as i32
+    }
+
+    fn surface(&self, x: i32, z: i32, y: i32) -> BlockType {
+        match &self.materials {
+            Some(materials) => {
+                let (px, pz) = self.pixel(x, z, materials.width(), materials.height());
+                let rgb = materials.get_pixel(px, pz);
+                BlockType::nearest_color((rgb[0], rgb[1], rgb[2]))
+            }
+            None => PerlinGenerator::get_block(y),
+        }
+    }
+}
+
+// an image with no pixels has nothing to tile or clamp to
+fn non_empty<I: GenericImageView>(image: I) -> ImageResult<I> {
+    if image.width() == 0 || image.height() == 0 {
+        let kind = ParameterErrorKind::Generic("image has no pixels".to_string());
+        return Err(ImageError::Parameter(ParameterError::from_kind(kind)));
+    }
+    Ok(image)
+}
+
+impl WorldGenerator for HeightmapGenerator {
+    fn generate(&self, coordinate: ChunkCoordinate) -> Chunk {
+        let mut chunk = Chunk::new(coordinate);
+        let origin = chunk.world_origin();
+        for x in 0..16 {
+            for z in 0..16 {
+                let (world_x, world_z) = (origin[0] + x, origin[2] + z);
+                let y = self.height(world_x, world_z);
+                fill_column(&mut chunk, x, z, y, self.surface(world_x, world_z, y));
             }
         }
         chunk
     }
+
+    fn surface_height(&self, x: i32, z: i32) -> i32 {
+        self.height(x, z) + HEIGHT_OFFSET
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb};
+
+    // a 2 x 1 heightmap, black on the left and white on the right
+    fn ramp(edges: EdgeMode) -> HeightmapGenerator {
+        HeightmapGenerator::new(GrayImage::from_fn(2, 1, |x, _| Luma([(x * 255) as u8])), None, edges)
+    }
+
+    #[test]
+    fn test_only_perlin_worlds_have_climate() {
+        let climate = PerlinGenerator::new(&Seed::new(3)).climate().unwrap();
+        let biomes: Vec<Biome> = (0..40).map(|i| climate.biome(i * 200, 0)).collect();
+        assert!(Biome::ALL.iter().filter(|b| biomes.contains(b)).count() > 1);
+        assert_eq!(biomes, (0..40).map(|i| Climate::new(&Seed::new(3)).biome(i * 200, 0)).collect::<Vec<_>>());
+
+        assert!(PlanarGenerator::new().climate().is_none());
+        assert!(ramp(EdgeMode::Tile).climate().is_none());
+    }
+
+    #[test]
+    fn test_heights_scale_from_black_to_white() {
+        let generator = ramp(EdgeMode::Clamp);
+        assert_eq!(generator.height(0, 0), generator.min_height);
+        assert_eq!(generator.height(1, 0), generator.max_height);
+        let grey = HeightmapGenerator::new(GrayImage::from_pixel(1, 1, Luma([128])), None, EdgeMode::Clamp);
+        assert_eq!(grey.height(0, 0), (grey.min_height + grey.max_height + 1) / 2);
+        assert_eq!(generator.surface_height(1, 0), generator.max_height + HEIGHT_OFFSET);
+    }
+
+    #[test]
+    fn test_height_range_can_be_set() {
+        let mut generator = ramp(EdgeMode::Clamp);
+        assert_eq!(generator.surface_height(0, 0), DEFAULT_HEIGHT_RANGE.0);
+        assert_eq!(generator.surface_height(1, 0), DEFAULT_HEIGHT_RANGE.1);
+        generator.set_height_range(20, 60);
+        assert_eq!(generator.surface_height(0, 0), 20);
+        assert_eq!(generator.surface_height(1, 0), 60);
+    }
+
+    #[test]
+    fn test_tiling_repeats_the_image() {
+        let generator = ramp(EdgeMode::Tile);
+        assert_eq!(generator.height(2, 0), generator.height(0, 0));
+        assert_eq!(generator.height(-1, 0), generator.height(1, 0));
+        assert_eq!(generator.height(3, 7), generator.height(1, 0));
+    }
+
+    #[test]
+    fn test_clamping_extends_the_edges() {
+        let generator = ramp(EdgeMode::Clamp);
+        assert_eq!(generator.height(-5, 0), generator.height(0, 0));
+        assert_eq!(generator.height(9, -3), generator.height(1, 0));
+    }
+
+    #[test]
+    fn test_materials_pick_the_nearest_block() {
+        let (r, g, b, _) = BlockType::STONE.color();
+        let stone = Rgb([(r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8]);
+        let materials = RgbImage::from_pixel(1, 1, stone);
+        let generator = HeightmapGenerator::new(GrayImage::from_pixel(1, 1, Luma([255])), Some(materials), EdgeMode::Tile);
+        let chunk = generator.generate(ChunkCoordinate::new(0, 0));
+        let top = chunk.get(&[3, generator.max_height, 4].into()).unwrap();
+        assert_eq!(top.block_type, BlockType::STONE);
+
+        // without materials the surface follows the height bands
+        let plain = HeightmapGenerator::new(GrayImage::from_pixel(1, 1, Luma([255])), None, EdgeMode::Tile);
+        let top = plain.generate(ChunkCoordinate::new(0, 0)).get(&[3, plain.max_height, 4].into()).unwrap().block_type;
+        assert_eq!(top, PerlinGenerator::get_block(plain.max_height));
+    }
+
+    #[test]
+    fn test_empty_images_are_rejected() {
+        assert!(non_empty(GrayImage::new(0, 0)).is_err());
+        assert!(non_empty(GrayImage::new(4, 0)).is_err());
+        assert!(non_empty(GrayImage::new(1, 1)).is_ok());
+    }
 }
@@ -0,0 +1,312 @@
+use crate::config::HEIGHT_OFFSET;
+use crate::engine::entity::{Entities, EntityId};
+use crate::engine::player::Player;
+use crate::engine::world::World;
+use glm::Vec3;
+use std::collections::VecDeque;
+
+pub const MAX_AIR: f32 = 10.0; // seconds of breath
+const AIR_REFILL: f32 = 5.0; // seconds of breath regained per second above water
+const DROWNING_DAMAGE: f32 = 2.0; // per second once out of air
+const SAFE_FALL_HEIGHT: f32 = 3.0; // blocks that can be fallen without harm
+const VOID_DEPTH: f32 = (-HEIGHT_OFFSET - 32) as f32; // anything below this is lost
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DamageKind {
+    Fall,
+    Drowning,
+    Attack,
+    Void,
+}
+
+impl DamageKind {
+    pub fn from_id(id: &str) -> Option<DamageKind> {
+        match id {
+            "fall" => Some(DamageKind::Fall),
+            "drowning" => Some(DamageKind::Drowning),
+            "attack" => Some(DamageKind::Attack),
+            "void" => Some(DamageKind::Void),
+            _ => None,
+        }
+    }
+}
+
+// fraction of each kind of damage that is ignored, the void can't be resisted
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Resistances {
+    pub fall: f32,
+    pub drowning: f32,
+    pub attack: f32,
+}
+
+impl Resistances {
+    pub fn get(&self, kind: DamageKind) -> f32 {
+        match kind {
+            DamageKind::Fall => self.fall,
+            DamageKind::Drowning => self.drowning,
+            DamageKind::Attack => self.attack,
+            DamageKind::Void => 0.,
+        }
+    }
+
+    pub fn set(&mut self, kind: DamageKind, fraction: f32) {
+        match kind {
+            DamageKind::Fall => self.fall = fraction,
+            DamageKind::Drowning => self.drowning = fraction,
+            DamageKind::Attack => self.attack = fraction,
+            DamageKind::Void => (),
+        }
+    }
+}
+
+// hit points and breath, shared by the player and any entity that can be hurt
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    pub air: f32,
+    pub resistances: Resistances,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Health {
+        Health {
+            current: max,
+            max,
+            air: MAX_AIR,
+            resistances: Resistances::default(),
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+
+    // returns the damage actually taken after resistances
+    pub fn damage(&mut self, kind: DamageKind, amount: f32) -> f32 {
+        let taken = match kind {
+            DamageKind::Void => self.current.max(0.),
+            _ => (amount * (1. - self.resistances.get(kind).min(1.))).min(self.current.max(0.)),
+        };
+        self.current -= taken;
+        taken
+    }
+
+    // uses up or refills breath, returning the drowning damage due this tick
+    pub fn breathe(&mut self, submerged: bool, dt: f32) -> f32 {
+        if !submerged {
+            self.air = (self.air + AIR_REFILL * dt).min(MAX_AIR);
+            return 0.;
+        }
+        self.air = (self.air - dt).max(0.);
+        if self.air > 0. {
+            0.
+        } else {
+            DROWNING_DAMAGE * dt
+        }
+    }
+}
+
+// damage for landing at a downward speed under the given gravity: a point per block fallen
+// past the safe height
+pub fn fall_damage(speed: f32, gravity: f32) -> f32 {
+    let height = speed * speed / (2. * gravity);
+    (height - SAFE_FALL_HEIGHT).max(0.).floor()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Player,
+    Entity(EntityId),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HealthEvent {
+    Damaged { target: Target, kind: DamageKind, amount: f32, remaining: f32 },
+    Died { target: Target, kind: DamageKind },
+    Respawned { position: Vec3 },
+}
+
+// Applies environmental damage to the player and to entities with Health, removes dead
+// entities and queues an event for everything that happens so the HUD and sound can react.
+pub struct Vitals {
+    events: VecDeque<HealthEvent>,
+}
+
+impl Vitals {
+    pub fn new() -> Vitals {
+        Vitals {
+            events: VecDeque::new(),
+        }
+    }
+
+    // everything that happened since the last call, oldest first
+    pub fn drain(&mut self) -> Vec<HealthEvent> {
+        self.events.drain(..).collect()
+    }
+
+    pub fn update_player(&mut self, player: &mut Player, dt: f32) {
+        let impact = player.take_impact();
+        if impact > 0. {
+            self.hurt_player(player, DamageKind::Fall, fall_damage(impact, player.gravity()));
+        }
+        let submerged = player.submerged().is_some();
+        let drowning = player.health_mut().breathe(submerged, dt);
+        if drowning > 0. {
+            self.hurt_player(player, DamageKind::Drowning, drowning);
+        }
+        if player.feet().y < VOID_DEPTH {
+            self.hurt_player(player, DamageKind::Void, 0.);
+        }
+    }
+
+    pub fn hurt_player(&mut self, player: &mut Player, kind: DamageKind, amount: f32) {
+        let health = player.health_mut();
+        if health.is_dead() {
+            return;
+        }
+        let taken = health.damage(kind, amount);
+        if taken > 0. {
+            self.damaged(Target::Player, kind, taken, health);
+        }
+    }
+
+    pub fn respawn(&mut self, player: &mut Player, eye: &Vec3) {
+        player.respawn(eye);
+        self.events.push_back(HealthEvent::Respawned { position: player.feet() });
+    }
+
+    pub fn update_entities(&mut self, world: &mut World, dt: f32) {
+        // read what each entity is touching first, the world can't be borrowed while they're hurt
+        let entities = world.entities();
+        let surroundings: Vec<(EntityId, Vec3, bool)> = entities
+            .ids()
+            .into_iter()
+            .filter(|id| entities.healths.contains_key(id))
+            .map(|id| {
+                let position = entities.transforms[&id].position;
+                (id, position, world.liquid_at(&World::block_at(&position)).is_some())
+            })
+            .collect();
+
+        let entities = world.entities_mut();
+        for (id, position, submerged) in surroundings {
+            let mut damage = Vec::new();
+            if let Some(body) = entities.bodies.get(&id) {
+                if body.impact > 0. && !submerged {
+                    damage.push((DamageKind::Fall, fall_damage(body.impact, body.gravity)));
+                }
+            }
+            if let Some(health) = entities.healths.get_mut(&id) {
+                damage.push((DamageKind::Drowning, health.breathe(submerged, dt)));
+            }
+            if position.y < VOID_DEPTH {
+                damage.push((DamageKind::Void, 0.));
+            }
+
+            for (kind, amount) in damage {
+                self.hurt_entity(entities, id, kind, amount);
+            }
+        }
+    }
+
+    // despawns the entity if this kills it
+    pub fn hurt_entity(&mut self, entities: &mut Entities, id: EntityId, kind: DamageKind, amount: f32) {
+        let health = match entities.healths.get_mut(&id) {
+            Some(health) => health,
+            None => return,
+        };
+        let taken = health.damage(kind, amount);
+        if taken > 0. {
+            self.damaged(Target::Entity(id), kind, taken, health);
+        }
+        if health.is_dead() {
+            entities.despawn(id);
+        }
+    }
+
+    fn damaged(&mut self, target: Target, kind: DamageKind, amount: f32, health: &Health) {
+        self.events.push_back(HealthEvent::Damaged { target, kind, amount, remaining: health.current });
+        if health.is_dead() {
+            self.events.push_back(HealthEvent::Died { target, kind });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::block::BlockType;
+    use crate::engine::entity::{Body, Transform};
+    use glm::vec3;
+
+    fn creature(entities: &mut Entities, position: Vec3, health: Health) -> EntityId {
+        let id = entities.spawn(Transform { position, velocity: vec3(0., 0., 0.) });
+        entities.bodies.insert(id, Body { size: 0.8, gravity: 20., friction: 8., on_ground: false, impact: 0. });
+        entities.healths.insert(id, health);
+        id
+    }
+
+    fn run(vitals: &mut Vitals, world: &mut World, seconds: f32) {
+        for _ in 0..(seconds * 60.) as usize {
+            world.tick(1. / 60.);
+            vitals.update_entities(world, 1. / 60.);
+        }
+    }
+
+    #[test]
+    fn test_resistances() {
+        let mut health = Health::new(20.);
+        health.resistances.set(DamageKind::Fall, 0.5);
+        assert_eq!(health.damage(DamageKind::Fall, 4.), 2.);
+        assert_eq!(health.damage(DamageKind::Attack, 4.), 4.);
+        assert_eq!(health.damage(DamageKind::Attack, 100.), 14., "damage is capped by what's left");
+        assert!(health.is_dead());
+
+        let mut health = Health::new(20.);
+        health.resistances.attack = 1.;
+        assert_eq!(health.damage(DamageKind::Attack, 5.), 0.);
+        assert_eq!(health.damage(DamageKind::Void, 0.), 20.);
+    }
+
+    #[test]
+    fn test_fall_damage_from_landing_speed() {
+        let speed = |height: f32| (2. * 20. * height).sqrt();
+        assert_eq!(fall_damage(speed(2.5), 20.), 0.);
+        assert_eq!(fall_damage(speed(4.5), 20.), 1.);
+        assert_eq!(fall_damage(speed(10.5), 20.), 7.);
+    }
+
+    #[test]
+    fn test_entities_take_fall_damage() {
+        let mut world = World::flat(0, false);
+        let mut vitals = Vitals::new();
+        let low = creature(world.entities_mut(), vec3(4., 18., 4.), Health::new(20.));
+        let high = creature(world.entities_mut(), vec3(8., 28., 8.), Health::new(20.));
+        run(&mut vitals, &mut world, 3.);
+
+        let healths = &world.entities().healths;
+        assert_eq!(healths[&low].current, 20.);
+        assert!(healths[&high].current < 14. && healths[&high].current > 10., "{}", healths[&high].current);
+        let events = vitals.drain();
+        assert!(events.iter().any(|e| matches!(e, HealthEvent::Damaged { target: Target::Entity(id), kind: DamageKind::Fall, .. } if *id == high)));
+    }
+
+    #[test]
+    fn test_drowning_kills_and_despawns() {
+        let mut world = World::flat(0, false);
+        for y in 17..20 {
+            world.set_block(&vec3(4, y, 4), BlockType::WATER);
+        }
+        let mut vitals = Vitals::new();
+        let id = creature(world.entities_mut(), vec3(4., 17., 4.), Health::new(4.));
+
+        run(&mut vitals, &mut world, MAX_AIR - 1.);
+        assert_eq!(world.entities().healths[&id].current, 4., "still holding breath");
+
+        run(&mut vitals, &mut world, 4.);
+        assert!(world.entities().is_empty());
+        let events = vitals.drain();
+        assert_eq!(events.last(), Some(&HealthEvent::Died { target: Target::Entity(id), kind: DamageKind::Drowning }));
+    }
+}
@@ -0,0 +1,55 @@
+use crate::engine::inventory::HOTBAR_SIZE;
+use crate::primitives::CrosshairVertex;
+use glium::vertex::VertexBufferSlice;
+use glium::{Display, VertexBuffer};
+
+// screen space layout of the hotbar strip along the bottom edge
+pub const SLOT_WIDTH: f32 = 0.1;
+pub const BOTTOM: f32 = -0.97;
+pub const TOP: f32 = -0.83;
+const SELECTION_INSET: f32 = 0.008;
+
+// Outline boxes for every slot followed by a smaller box per slot used to mark the selection,
+// drawn as a LinesList with the crosshair program.
+pub struct Hotbar {
+    pub vbo: VertexBuffer<CrosshairVertex>,
+}
+
+impl Hotbar {
+    pub fn new(display: &Display) -> Hotbar {
+        let mut vertices: Vec<CrosshairVertex> = Vec::new();
+        for inset in [0., SELECTION_INSET].iter() {
+            for slot in 0..HOTBAR_SIZE {
+                let left = Self::slot_left(slot) + inset;
+                let right = left + SLOT_WIDTH - 2. * inset;
+                Self::add_box(&mut vertices, left, BOTTOM + inset, right, TOP - inset);
+            }
+        }
+
+        let vbo = VertexBuffer::new(display, &vertices).expect("to create vb");
+        Hotbar {
+            vbo
+        }
+    }
+
+    pub fn slot_left(slot: usize) -> f32 {
+        (slot as f32 - HOTBAR_SIZE as f32 / 2.) * SLOT_WIDTH
+    }
+
+    pub fn outlines(&self) -> VertexBufferSlice<'_, CrosshairVertex> {
+        self.vbo.slice(0..HOTBAR_SIZE * 8).unwrap()
+    }
+
+    pub fn selection(&self, slot: usize) -> VertexBufferSlice<'_, CrosshairVertex> {
+        let start = (HOTBAR_SIZE + slot) * 8;
+        self.vbo.slice(start..start + 8).unwrap()
+    }
+
+    pub fn add_box(vertices: &mut Vec<CrosshairVertex>, left: f32, bottom: f32, right: f32, top: f32) {
+        let corners = [[left, bottom], [right, bottom], [right, top], [left, top]];
+        for i in 0..4 {
+            vertices.push(CrosshairVertex::new(corners[i]));
+            vertices.push(CrosshairVertex::new(corners[(i + 1) % 4]));
+        }
+    }
+}
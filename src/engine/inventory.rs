@@ -0,0 +1,217 @@
+use crate::engine::block::BlockType;
+
+pub const HOTBAR_SIZE: usize = 9;
+pub const INVENTORY_SIZE: usize = 36; // hotbar plus three rows of storage
+pub const STACK_SIZE: u32 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    Creative, // every block available, placing never runs out
+    Survival, // blocks have to be collected and are used up when placed
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ItemStack {
+    pub block: BlockType,
+    pub count: u32,
+}
+
+// The first HOTBAR_SIZE slots are the hotbar, the selected one is what gets placed.
+pub struct Inventory {
+    mode: GameMode,
+    slots: Vec<Option<ItemStack>>,
+    selected: usize,
+}
+
+impl Inventory {
+    pub fn new(mode: GameMode) -> Inventory {
+        let mut inventory = Inventory {
+            mode,
+            slots: vec![None; INVENTORY_SIZE],
+            selected: 0,
+        };
+        if mode == GameMode::Creative {
+            // building blocks on the hotbar, and every circuit part together one page along
+            let (circuits, blocks): (Vec<BlockType>, Vec<BlockType>) =
+                BlockType::ALL.iter().filter(|b| b.item() == **b).partition(|b| b.is_circuit());
+            for (row, items) in [blocks, circuits].iter().enumerate() {
+                for (slot, block) in items.iter().enumerate() {
+                    inventory.slots[row * HOTBAR_SIZE + slot] = Some(ItemStack { block: *block, count: STACK_SIZE });
+                }
+            }
+        }
+        inventory
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    pub fn hotbar(&self) -> &[Option<ItemStack>] {
+        &self.slots[..HOTBAR_SIZE]
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SIZE {
+            self.selected = slot;
+        }
+    }
+
+    // positive steps move right along the hotbar, wrapping at the ends
+    pub fn scroll(&mut self, steps: i32) {
+        self.selected = (self.selected as i32 + steps).rem_euclid(HOTBAR_SIZE as i32) as usize;
+    }
+
+    // Brings the next row of storage down into the hotbar and sends the hotbar to the back, so
+    // paging through every row reaches every slot.
+    pub fn cycle_rows(&mut self) {
+        self.slots.rotate_left(HOTBAR_SIZE);
+    }
+
+    pub fn selected_block(&self) -> Option<BlockType> {
+        self.slots[self.selected].map(|stack| stack.block)
+    }
+
+    // uses up one of the selected block after it was placed
+    pub fn consume_selected(&mut self) {
+        if self.mode == GameMode::Creative {
+            return;
+        }
+        if let Some(stack) = self.slots[self.selected].as_mut() {
+            stack.count -= 1;
+            if stack.count == 0 {
+                self.slots[self.selected] = None;
+            }
+        }
+    }
+
+    // hands back blocks that were taken with consume_selected, returning what didn't fit. In
+    // creative nothing was taken, so nothing comes back.
+    pub fn restore(&mut self, block: BlockType, count: u32) -> u32 {
+        if self.mode == GameMode::Creative {
+            return 0;
+        }
+        self.add(block, count)
+    }
+
+    // tops up existing stacks first, then fills empty slots, returning what didn't fit
+    pub fn add(&mut self, block: BlockType, mut count: u32) -> u32 {
+        for stack in self.slots.iter_mut().flatten() {
+            if stack.block == block && stack.count < STACK_SIZE {
+                let moved = count.min(STACK_SIZE - stack.count);
+                stack.count += moved;
+                count -= moved;
+            }
+        }
+        for slot in self.slots.iter_mut().filter(|s| s.is_none()) {
+            if count == 0 {
+                break;
+            }
+            let moved = count.min(STACK_SIZE);
+            *slot = Some(ItemStack { block, count: moved });
+            count -= moved;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_creative_offers_every_block() {
+        let mut inventory = Inventory::new(GameMode::Creative);
+        let mut selectable = Vec::new();
+        for _ in 0..INVENTORY_SIZE / HOTBAR_SIZE {
+            for slot in 0..HOTBAR_SIZE {
+                inventory.select(slot);
+                selectable.extend(inventory.selected_block());
+            }
+            inventory.cycle_rows();
+        }
+        for block in BlockType::ALL.iter().map(|b| b.item()) {
+            assert!(selectable.contains(&block), "{:?} can't be selected", block);
+        }
+
+        // the circuit parts are all one page away from the building blocks
+        inventory.cycle_rows();
+        assert!(inventory.hotbar().iter().flatten().all(|s| s.block.is_circuit()));
+    }
+
+    #[test]
+    fn test_survival_consumes_placed_blocks() {
+        let mut inventory = Inventory::new(GameMode::Survival);
+        assert_eq!(inventory.selected_block(), None);
+        inventory.add(BlockType::SAND, 2);
+        assert_eq!(inventory.selected_block(), Some(BlockType::SAND));
+        inventory.consume_selected();
+        inventory.consume_selected();
+        assert_eq!(inventory.selected_block(), None);
+    }
+
+    // what the inventory holds of a block across all its slots
+    fn total(inventory: &Inventory, block: BlockType) -> u32 {
+        inventory.slots.iter().flatten().filter(|s| s.block == block).map(|s| s.count).sum()
+    }
+
+    #[test]
+    fn test_survival_gets_grid_blocks_back() {
+        let mut inventory = Inventory::new(GameMode::Survival);
+        inventory.add(BlockType::SAND, 3);
+        inventory.consume_selected();
+        inventory.consume_selected();
+        assert_eq!(inventory.restore(BlockType::SAND, 2), 0);
+        assert_eq!(total(&inventory, BlockType::SAND), 3);
+    }
+
+    #[test]
+    fn test_creative_grid_blocks_arent_duplicated() {
+        let mut inventory = Inventory::new(GameMode::Creative);
+        let block = inventory.selected_block().unwrap();
+        let before = total(&inventory, block);
+        inventory.consume_selected();
+        assert_eq!(inventory.restore(block, 1), 0);
+        assert_eq!(total(&inventory, block), before);
+    }
+
+    #[test]
+    fn test_add_merges_into_stacks() {
+        let mut inventory = Inventory::new(GameMode::Survival);
+        assert_eq!(inventory.add(BlockType::DIRT, 40), 0);
+        assert_eq!(inventory.add(BlockType::DIRT, 40), 0);
+        assert_eq!(inventory.slots[0], Some(ItemStack { block: BlockType::DIRT, count: 64 }));
+        assert_eq!(inventory.slots[1], Some(ItemStack { block: BlockType::DIRT, count: 16 }));
+        assert_eq!(inventory.add(BlockType::STONE, STACK_SIZE * INVENTORY_SIZE as u32), 2 * STACK_SIZE);
+    }
+
+    #[test]
+    fn test_overflow_can_be_cycled_into_the_hotbar() {
+        let mut inventory = Inventory::new(GameMode::Survival);
+        inventory.add(BlockType::STONE, STACK_SIZE * HOTBAR_SIZE as u32);
+        inventory.add(BlockType::SAND, 1);
+        assert!(inventory.hotbar().iter().flatten().all(|s| s.block == BlockType::STONE));
+
+        inventory.cycle_rows();
+        inventory.select(0);
+        assert_eq!(inventory.selected_block(), Some(BlockType::SAND));
+        // the old hotbar comes back round after the last row
+        for _ in 0..INVENTORY_SIZE / HOTBAR_SIZE - 1 {
+            inventory.cycle_rows();
+        }
+        assert_eq!(inventory.selected_block(), Some(BlockType::STONE));
+    }
+
+    #[test]
+    fn test_scroll_wraps() {
+        let mut inventory = Inventory::new(GameMode::Creative);
+        inventory.scroll(-1);
+        assert_eq!(inventory.selected(), HOTBAR_SIZE - 1);
+        inventory.scroll(2);
+        assert_eq!(inventory.selected(), 1);
+    }
+}
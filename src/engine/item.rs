@@ -0,0 +1,186 @@
+use crate::engine::block::BlockType;
+use crate::engine::entity::{Appearance, Body, Entities, EntityId, Item, Transform};
+use crate::engine::inventory::{Inventory, STACK_SIZE};
+use glm::{vec3, Vec3};
+use std::collections::HashMap;
+
+pub const ITEM_SIZE: f32 = 0.25;
+const GRAVITY: f32 = 20.0;
+const POP_SPEED: f32 = 4.0; // upwards speed when a block breaks
+const GROUND_FRICTION: f32 = 8.0;
+const MERGE_RADIUS: f32 = 1.0;
+const PICKUP_RANGE: f32 = 1.5;
+const PICKUP_DELAY: f32 = 0.5; // seconds before a fresh drop can be collected
+const DESPAWN_AFTER: f32 = 300.0;
+
+// Gameplay rules for dropped items: merging, despawning and pickup. Falling is left to the
+// entity physics, so all of this runs on the simulation tick without a display.
+pub struct Items {
+    collected: HashMap<BlockType, u32>,
+}
+
+impl Items {
+    pub fn new() -> Items {
+        Items {
+            collected: HashMap::new(),
+        }
+    }
+
+    // total picked up of a block type since the start
+    pub fn collected(&self, block: BlockType) -> u32 {
+        self.collected.get(&block).copied().unwrap_or(0)
+    }
+
+    pub fn spawn(&self, entities: &mut Entities, block: BlockType, count: u32, position: Vec3) -> EntityId {
+        let id = entities.spawn(Transform {
+            position,
+            velocity: vec3(0., POP_SPEED, 0.),
+        });
+        entities.bodies.insert(id, Body {
+            size: ITEM_SIZE,
+            gravity: GRAVITY,
+            friction: GROUND_FRICTION,
+            on_ground: false,
+            impact: 0.,
+        });
+        entities.appearances.insert(id, Appearance {
+            color: block.color(),
+            scale: ITEM_SIZE,
+        });
+        entities.items.insert(id, Item { block, count, age: 0. });
+        id
+    }
+
+    pub fn update(&mut self, entities: &mut Entities, dt: f32) {
+        let mut expired = Vec::new();
+        for (id, item) in entities.items.iter_mut() {
+            item.age += dt;
+            if item.age >= DESPAWN_AFTER {
+                expired.push(*id);
+            }
+        }
+        for id in expired {
+            entities.despawn(id);
+        }
+        Self::merge(entities);
+    }
+
+    // folds resting items of the same type into the oldest nearby stack
+    fn merge(entities: &mut Entities) {
+        let resting = |entities: &Entities, id: &EntityId| entities.bodies.get(id).map_or(false, |b| b.on_ground);
+        let ids: Vec<EntityId> = entities.ids().into_iter().filter(|id| entities.items.contains_key(id)).collect();
+        for (n, a) in ids.iter().enumerate() {
+            for b in ids[n + 1..].iter() {
+                let (item_a, item_b) = match (entities.items.get(a), entities.items.get(b)) {
+                    (Some(item_a), Some(item_b)) => (*item_a, *item_b),
+                    _ => continue,
+                };
+                let mergeable = item_a.block == item_b.block
+                    && resting(entities, a)
+                    && resting(entities, b)
+                    && item_a.count + item_b.count <= STACK_SIZE
+                    && glm::distance(&entities.transforms[a].position, &entities.transforms[b].position) <= MERGE_RADIUS;
+                if mergeable {
+                    let into = entities.items.get_mut(a).unwrap();
+                    into.count += item_b.count;
+                    into.age = into.age.min(item_b.age);
+                    entities.despawn(*b);
+                }
+            }
+        }
+    }
+
+    // moves items within range of the collector into the inventory, leaving what doesn't fit
+    pub fn pickup(&mut self, entities: &mut Entities, collector: &Vec3, inventory: &mut Inventory) {
+        let mut emptied = Vec::new();
+        for (id, item) in entities.items.iter_mut() {
+            let position = entities.transforms[id].position;
+            if item.age < PICKUP_DELAY || glm::distance(&position, collector) > PICKUP_RANGE {
+                continue;
+            }
+            let leftover = inventory.add(item.block, item.count);
+            *self.collected.entry(item.block).or_insert(0) += item.count - leftover;
+            item.count = leftover;
+            if leftover == 0 {
+                emptied.push(*id);
+            }
+        }
+        for id in emptied {
+            entities.despawn(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::inventory::GameMode;
+    use crate::engine::world::World;
+
+    fn run(items: &mut Items, world: &mut World, seconds: f32) {
+        for _ in 0..(seconds * 60.) as usize {
+            world.tick(1. / 60.);
+            items.update(world.entities_mut(), 1. / 60.);
+        }
+    }
+
+    fn item_entities(world: &World) -> Vec<(Vec3, Item)> {
+        let entities = world.entities();
+        entities.ids().iter().map(|id| (entities.transforms[id].position, entities.items[id])).collect()
+    }
+
+    #[test]
+    fn test_items_fall_and_land() {
+        let mut world = World::flat(0, false);
+        let mut items = Items::new();
+        items.spawn(world.entities_mut(), BlockType::SAND, 1, vec3(4., 20., 4.));
+        run(&mut items, &mut world, 3.);
+
+        let (position, _) = item_entities(&world)[0];
+        assert!((position.y - (16.5 + ITEM_SIZE / 2.)).abs() < 1e-4);
+        let id = world.entities().ids()[0];
+        assert!(world.entities().bodies[&id].on_ground);
+    }
+
+    #[test]
+    fn test_nearby_items_merge() {
+        let mut world = World::flat(0, false);
+        let mut items = Items::new();
+        items.spawn(world.entities_mut(), BlockType::DIRT, 1, vec3(4., 17., 4.));
+        items.spawn(world.entities_mut(), BlockType::DIRT, 2, vec3(4.5, 17., 4.));
+        items.spawn(world.entities_mut(), BlockType::STONE, 1, vec3(4., 17., 4.5));
+        run(&mut items, &mut world, 2.);
+
+        let remaining = item_entities(&world);
+        assert_eq!(remaining.len(), 2);
+        let (_, dirt) = remaining.iter().find(|(_, i)| i.block == BlockType::DIRT).unwrap();
+        assert_eq!(dirt.count, 3);
+    }
+
+    #[test]
+    fn test_items_despawn() {
+        let mut world = World::flat(0, false);
+        let mut items = Items::new();
+        items.spawn(world.entities_mut(), BlockType::DIRT, 1, vec3(4., 17., 4.));
+        items.update(world.entities_mut(), DESPAWN_AFTER);
+        assert!(world.entities().is_empty());
+    }
+
+    #[test]
+    fn test_pickup_in_range() {
+        let mut world = World::flat(0, false);
+        let mut items = Items::new();
+        let mut inventory = Inventory::new(GameMode::Survival);
+        items.spawn(world.entities_mut(), BlockType::SAND, 2, vec3(4., 17., 4.));
+        items.spawn(world.entities_mut(), BlockType::SAND, 1, vec3(12., 17., 12.));
+
+        items.pickup(world.entities_mut(), &vec3(4., 17., 4.), &mut inventory);
+        assert_eq!(items.collected(BlockType::SAND), 0, "picked up before the delay");
+
+        run(&mut items, &mut world, 1.);
+        items.pickup(world.entities_mut(), &vec3(4., 17., 4.), &mut inventory);
+        assert_eq!(items.collected(BlockType::SAND), 2);
+        assert_eq!(world.entities().len(), 1);
+        assert_eq!(inventory.selected_block(), Some(BlockType::SAND));
+    }
+}
@@ -0,0 +1,400 @@
+use crate::config::HEIGHT_OFFSET;
+use crate::engine::block::BlockType;
+use crate::engine::chunk::{BlockCoordinate, Chunk, ChunkCoordinate, CHUNK_SIZE};
+use crate::engine::world::{World, SKY_HEIGHT};
+use glm::{vec3, IVec3};
+use std::collections::VecDeque;
+
+pub const MAX_LIGHT: u8 = 15;
+// fraction of full brightness left in complete darkness, so caves aren't pure black
+const AMBIENT: f32 = 0.04;
+// each level of light is this much dimmer than the one above it
+const FALLOFF: f32 = 0.8;
+
+// light travelling through liquid fades this much faster than through air
+const LIQUID_ABSORPTION: u8 = 1;
+
+const DIRECTIONS: [(i32, i32, i32); 6] = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+const DOWN: (i32, i32, i32) = (0, -1, 0);
+
+// cells of a chunk column that hold light, from world height 0 up to the sky
+const COLUMN_HEIGHT: i32 = SKY_HEIGHT;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Sky,   // daylight, full strength straight down through open air
+    Block, // given off by emissive blocks
+}
+
+const CHANNELS: [Channel; 2] = [Channel::Sky, Channel::Block];
+
+// Sky and block light for every cell of a chunk column, a nibble each. Cells above the
+// column are open sky and cells below it are dark.
+pub struct LightMap {
+    cells: Vec<u8>,
+}
+
+impl LightMap {
+    pub fn new() -> LightMap {
+        LightMap {
+            cells: vec![0; (CHUNK_SIZE * CHUNK_SIZE * COLUMN_HEIGHT) as usize],
+        }
+    }
+
+    fn index(local: &BlockCoordinate) -> Option<usize> {
+        let y = local[1] + HEIGHT_OFFSET;
+        if !(0..COLUMN_HEIGHT).contains(&y) {
+            return None;
+        }
+        Some(((y * CHUNK_SIZE + local[2]) * CHUNK_SIZE + local[0]) as usize)
+    }
+
+    pub fn get(&self, local: &BlockCoordinate, channel: Channel) -> u8 {
+        match (LightMap::index(local), channel) {
+            (Some(i), Channel::Sky) => self.cells[i] >> 4,
+            (Some(i), Channel::Block) => self.cells[i] & 0xf,
+            (None, Channel::Sky) if local[1] >= 0 => MAX_LIGHT,
+            (None, _) => 0,
+        }
+    }
+
+    // false for cells outside the column, which can't be changed
+    pub fn set(&mut self, local: &BlockCoordinate, channel: Channel, value: u8) -> bool {
+        let i = match LightMap::index(local) {
+            Some(i) => i,
+            None => return false,
+        };
+        self.cells[i] = match channel {
+            Channel::Sky => (self.cells[i] & 0xf) | (value << 4),
+            Channel::Block => (self.cells[i] & 0xf0) | value,
+        };
+        true
+    }
+}
+
+// how bright a light level looks, from AMBIENT in the dark to 1 in full light
+pub fn brightness(light: u8) -> f32 {
+    AMBIENT + (1. - AMBIENT) * FALLOFF.powi((MAX_LIGHT - light.min(MAX_LIGHT)) as i32)
+}
+
+// What the flood fill needs from the space it lights, so a chunk can be lit on its own as
+// it's generated and the world can be kept up to date across chunks afterwards.
+pub trait Cells {
+    fn block(&self, cell: &IVec3) -> Option<BlockType>;
+    fn light(&self, cell: &IVec3, channel: Channel) -> u8;
+    // false where light can't be stored
+    fn set_light(&mut self, cell: &IVec3, channel: Channel, value: u8) -> bool;
+}
+
+impl Cells for World {
+    fn block(&self, cell: &IVec3) -> Option<BlockType> {
+        self.get_block(cell).copied()
+    }
+
+    fn light(&self, cell: &IVec3, channel: Channel) -> u8 {
+        self.light_value(cell, channel)
+    }
+
+    fn set_light(&mut self, cell: &IVec3, channel: Channel, value: u8) -> bool {
+        World::set_light(self, cell, channel, value)
+    }
+}
+
+// in chunk coordinates, anything outside the chunk is dark open space that can't be lit
+impl Cells for Chunk {
+    fn block(&self, cell: &IVec3) -> Option<BlockType> {
+        self.get(&[cell.x, cell.y, cell.z].into()).map(|b| b.block_type)
+    }
+
+    fn light(&self, cell: &IVec3, channel: Channel) -> u8 {
+        if !inside(cell) {
+            return 0;
+        }
+        Chunk::light(self, &[cell.x, cell.y, cell.z].into(), channel)
+    }
+
+    fn set_light(&mut self, cell: &IVec3, channel: Channel, value: u8) -> bool {
+        inside(cell) && Chunk::set_light(self, &[cell.x, cell.y, cell.z].into(), channel, value)
+    }
+}
+
+fn inside(local: &IVec3) -> bool {
+    (0..CHUNK_SIZE).contains(&local.x) && (0..CHUNK_SIZE).contains(&local.z)
+}
+
+// Lights a freshly generated or loaded chunk by itself: sunlight down every open column
+// and light from its emissive blocks, flooded through the chunk. Only cells that can light
+// something dimmer next to them are flooded from, most open sky can't.
+pub fn light_chunk(chunk: &mut Chunk) {
+    let (bottom, top) = (-HEIGHT_OFFSET, COLUMN_HEIGHT - HEIGHT_OFFSET);
+    let mut sky = VecDeque::new();
+    let mut block = VecDeque::new();
+
+    // lowest cell of each column that still gets full daylight
+    let mut open = [[top; CHUNK_SIZE as usize]; CHUNK_SIZE as usize];
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            let mut daylight = MAX_LIGHT;
+            for y in (bottom..top).rev() {
+                let cell = vec3(x, y, z);
+                daylight = match chunk.block(&cell) {
+                    Some(b) if !b.is_transparent() => 0,
+                    Some(b) if b.is_liquid() => daylight.saturating_sub(1 + LIQUID_ABSORPTION),
+                    _ => daylight,
+                };
+                if daylight == 0 {
+                    break;
+                }
+                Cells::set_light(chunk, &cell, Channel::Sky, daylight);
+                if daylight == MAX_LIGHT {
+                    open[x as usize][z as usize] = y;
+                } else {
+                    sky.push_back(cell);
+                }
+            }
+        }
+    }
+    // full daylight only spreads where the column next to it is shaded
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            let shaded = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .iter()
+                .map(|(dx, dz)| (x + dx, z + dz))
+                .filter(|(nx, nz)| inside(&vec3(*nx, 0, *nz)))
+                .map(|(nx, nz)| open[nx as usize][nz as usize])
+                .max()
+                .unwrap_or(bottom);
+            for y in open[x as usize][z as usize]..shaded {
+                sky.push_back(vec3(x, y, z));
+            }
+        }
+    }
+
+    for (local, emission) in chunk.emitters() {
+        let cell = vec3(local[0], local[1], local[2]);
+        Cells::set_light(chunk, &cell, Channel::Block, emission);
+        block.push_back(cell);
+    }
+
+    spread(chunk, Channel::Sky, sky);
+    spread(chunk, Channel::Block, block);
+}
+
+// Lets light across the borders between a chunk that has just been added to the world and
+// the loaded chunks around it, in both directions.
+pub fn stitch(world: &mut World, coordinate: ChunkCoordinate) {
+    let origin = vec3(coordinate[0] * CHUNK_SIZE, 0, coordinate[1] * CHUNK_SIZE);
+    let mut sky = VecDeque::new();
+    let mut block = VecDeque::new();
+    // first cell on the inside of each edge, the step out across it and the step along it
+    let edges = [
+        (vec3(0, 0, 0), vec3(-1, 0, 0), vec3(0, 0, 1)),
+        (vec3(CHUNK_SIZE - 1, 0, 0), vec3(1, 0, 0), vec3(0, 0, 1)),
+        (vec3(0, 0, 0), vec3(0, 0, -1), vec3(1, 0, 0)),
+        (vec3(0, 0, CHUNK_SIZE - 1), vec3(0, 0, 1), vec3(1, 0, 0)),
+    ];
+    for (start, out, along) in edges.iter() {
+        if !world.is_loaded(&(origin + start + out)) {
+            continue;
+        }
+        for i in 0..CHUNK_SIZE {
+            for y in 0..COLUMN_HEIGHT {
+                let inner = origin + start + along * i + vec3(0, y, 0);
+                let outer = inner + out;
+                for (channel, queue) in [(Channel::Sky, &mut sky), (Channel::Block, &mut block)].iter_mut() {
+                    let (a, b) = (world.light_value(&inner, *channel), world.light_value(&outer, *channel));
+                    if a > b + 1 {
+                        queue.push_back(inner);
+                    } else if b > a + 1 {
+                        queue.push_back(outer);
+                    }
+                }
+            }
+        }
+    }
+    spread(world, Channel::Sky, sky);
+    spread(world, Channel::Block, block);
+}
+
+// Brings light up to date after the block changed: takes away the light it held and any
+// that depended on it, then lets its own emission and the surrounding light back in.
+pub fn update(world: &mut World, block: &IVec3) {
+    for channel in CHANNELS.iter() {
+        let old = world.light_value(block, *channel);
+        let mut refill = VecDeque::new();
+        if old > 0 {
+            world.set_light(block, *channel, 0);
+            darken(world, *channel, block, old, &mut refill);
+        }
+        let emission = world.get_block(block).map_or(0, |b| b.emission());
+        if *channel == Channel::Block && emission > 0 {
+            world.set_light(block, *channel, emission);
+            refill.push_back(*block);
+        }
+        for (x, y, z) in DIRECTIONS.iter() {
+            refill.push_back(block + vec3(*x, *y, *z));
+        }
+        spread(world, *channel, refill);
+    }
+}
+
+// light the neighbour in the given direction receives from a cell lit to `light`
+fn passed<C: Cells>(cells: &C, channel: Channel, light: u8, direction: (i32, i32, i32), to: &IVec3) -> u8 {
+    match cells.block(to) {
+        Some(b) if !b.is_transparent() => 0,
+        Some(b) if b.is_liquid() => light.saturating_sub(1 + LIQUID_ABSORPTION),
+        _ if channel == Channel::Sky && direction == DOWN && light == MAX_LIGHT => MAX_LIGHT,
+        _ => light.saturating_sub(1),
+    }
+}
+
+// breadth first flood from every queued cell, raising anything it can light brighter
+fn spread<C: Cells>(cells: &mut C, channel: Channel, mut queue: VecDeque<IVec3>) {
+    while let Some(cell) = queue.pop_front() {
+        let light = cells.light(&cell, channel);
+        if light <= 1 {
+            continue;
+        }
+        for direction in DIRECTIONS.iter() {
+            let neighbour = cell + vec3(direction.0, direction.1, direction.2);
+            // most neighbours are already as bright as this cell could make them
+            let sunbeam = channel == Channel::Sky && *direction == DOWN && light == MAX_LIGHT;
+            let current = cells.light(&neighbour, channel);
+            if current >= light || (current + 1 == light && !sunbeam) {
+                continue;
+            }
+            let value = passed(cells, channel, light, *direction, &neighbour);
+            if value > current && cells.set_light(&neighbour, channel, value) {
+                queue.push_back(neighbour);
+            }
+        }
+    }
+}
+
+// Clears the light that flowed out of a cell that was lit to `light`. Neighbours brighter
+// than what it could have given them have their own source, they go in refill to flood
+// back into the cleared space.
+fn darken(world: &mut World, channel: Channel, start: &IVec3, light: u8, refill: &mut VecDeque<IVec3>) {
+    let mut queue = VecDeque::new();
+    queue.push_back((*start, light));
+    while let Some((cell, light)) = queue.pop_front() {
+        for direction in DIRECTIONS.iter() {
+            let neighbour = cell + vec3(direction.0, direction.1, direction.2);
+            let value = world.light_value(&neighbour, channel);
+            if value == 0 {
+                continue;
+            }
+            let sunbeam = channel == Channel::Sky && *direction == DOWN && light == MAX_LIGHT;
+            if value < light || sunbeam {
+                if !world.set_light(&neighbour, channel, 0) {
+                    continue;
+                }
+                queue.push_back((neighbour, value));
+                let emission = world.get_block(&neighbour).map_or(0, |b| b.emission());
+                if channel == Channel::Block && emission > 0 {
+                    world.set_light(&neighbour, channel, emission);
+                    refill.push_back(neighbour);
+                }
+            } else {
+                refill.push_back(neighbour);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point2;
+
+    // a closed stone box with its inside from (2, 17, 2) to (6, 19, 6)
+    fn build_box(world: &mut World) {
+        for x in 1..8 {
+            for z in 1..8 {
+                for y in 17..21 {
+                    let wall = x == 1 || x == 7 || z == 1 || z == 7 || y == 20;
+                    if wall {
+                        world.set_block(&vec3(x, y, z), BlockType::STONE);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_open_sky_is_fully_lit() {
+        let world = World::flat(0, false);
+        assert_eq!(world.light(&vec3(4, 17, 4)), MAX_LIGHT);
+        assert_eq!(world.light(&vec3(4, 40, 4)), MAX_LIGHT);
+        assert_eq!(world.light(&vec3(4, 16, 4)), 0, "inside the floor");
+        assert_eq!(world.light(&vec3(4, 15, 4)), 0, "below the floor");
+    }
+
+    #[test]
+    fn test_enclosed_space_is_dark_until_opened() {
+        let mut world = World::flat(0, false);
+        build_box(&mut world);
+        assert_eq!(world.light(&vec3(4, 18, 4)), 0);
+
+        world.remove_block(&vec3(4, 20, 4));
+        assert_eq!(world.light(&vec3(4, 18, 4)), MAX_LIGHT, "sunlight falls straight in");
+        assert_eq!(world.light(&vec3(2, 18, 4)), MAX_LIGHT - 2);
+
+        world.set_block(&vec3(4, 20, 4), BlockType::STONE);
+        assert_eq!(world.light(&vec3(4, 18, 4)), 0);
+        assert_eq!(world.light(&vec3(2, 18, 4)), 0);
+    }
+
+    #[test]
+    fn test_emissive_blocks_light_their_surroundings() {
+        let mut world = World::flat(0, false);
+        build_box(&mut world);
+        world.set_block(&vec3(2, 17, 2), BlockType::TORCH);
+        let torch = BlockType::TORCH.emission();
+        assert_eq!(world.block_light(&vec3(2, 17, 2)), torch);
+        assert_eq!(world.block_light(&vec3(3, 17, 2)), torch - 1);
+        assert_eq!(world.block_light(&vec3(6, 19, 6)), torch - 10);
+        assert_eq!(world.block_light(&vec3(0, 17, 2)), 0, "the walls keep it in");
+
+        world.remove_block(&vec3(2, 17, 2));
+        assert!((0..8).all(|x| world.block_light(&vec3(x, 17, 2)) == 0));
+    }
+
+    #[test]
+    fn test_removing_one_of_two_lights_keeps_the_other() {
+        let mut world = World::flat(0, false);
+        world.set_block(&vec3(2, 17, 2), BlockType::TORCH);
+        world.set_block(&vec3(8, 17, 2), BlockType::TORCH);
+        world.remove_block(&vec3(2, 17, 2));
+        let torch = BlockType::TORCH.emission();
+        assert_eq!(world.block_light(&vec3(5, 17, 2)), torch - 3);
+        assert_eq!(world.block_light(&vec3(2, 17, 2)), torch - 6);
+    }
+
+    #[test]
+    fn test_light_crosses_chunk_borders() {
+        let mut world = World::flat(0, false);
+        world.set_block(&vec3(15, 17, 4), BlockType::TORCH);
+        world.generate_chunk(Point2::new(1, 0));
+        let torch = BlockType::TORCH.emission();
+        assert_eq!(world.block_light(&vec3(17, 17, 4)), torch - 2, "shines into a chunk loaded later");
+
+        world.set_block(&vec3(16, 17, 8), BlockType::TORCH);
+        assert_eq!(world.block_light(&vec3(14, 17, 8)), torch - 2);
+    }
+
+    #[test]
+    fn test_water_dims_sunlight() {
+        let mut world = World::flat(0, false);
+        // a well of water walled in on every side, open to the sky
+        for y in 17..20 {
+            for (x, z) in [(3, 4), (5, 4), (4, 3), (4, 5)].iter() {
+                world.set_block(&vec3(*x, y, *z), BlockType::STONE);
+            }
+            world.set_block(&vec3(4, y, 4), BlockType::WATER);
+        }
+        assert_eq!(world.sky_light(&vec3(4, 20, 4)), MAX_LIGHT);
+        assert_eq!(world.sky_light(&vec3(4, 19, 4)), MAX_LIGHT - 2);
+        assert_eq!(world.sky_light(&vec3(4, 17, 4)), MAX_LIGHT - 6);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::engine::atlas::{Atlas, Face};
+use crate::engine::block::BlockType;
+use crate::engine::light::{self, Cells, Channel};
+use crate::primitives::TextureVertex;
+use glm::{vec3, IVec3, Vec3};
+
+// Each face's normal and corners, as offsets from the block's centre in half blocks, wound
+// the same way as the cube mesh so backface culling keeps the outside.
+const FACES: [([i32; 3], [[i32; 3]; 4]); 6] = [
+    ([1, 0, 0], [[1, 1, -1], [1, 1, 1], [1, -1, 1], [1, -1, -1]]),
+    ([-1, 0, 0], [[-1, -1, -1], [-1, -1, 1], [-1, 1, 1], [-1, 1, -1]]),
+    ([0, 1, 0], [[-1, 1, 1], [1, 1, 1], [1, 1, -1], [-1, 1, -1]]),
+    ([0, -1, 0], [[1, -1, -1], [1, -1, 1], [-1, -1, 1], [-1, -1, -1]]),
+    ([0, 0, 1], [[1, -1, 1], [1, 1, 1], [-1, 1, 1], [-1, -1, 1]]),
+    ([0, 0, -1], [[-1, -1, -1], [-1, 1, -1], [1, 1, -1], [1, -1, -1]]),
+];
+
+// the directions a face's texture runs across and up it, seen from outside the block, in
+// FACES order
+const TEXTURE_AXES: [([i32; 3], [i32; 3]); 6] = [
+    ([0, 0, -1], [0, 1, 0]),
+    ([0, 0, 1], [0, 1, 0]),
+    ([1, 0, 0], [0, 0, -1]),
+    ([1, 0, 0], [0, 0, 1]),
+    ([1, 0, 0], [0, 1, 0]),
+    ([-1, 0, 0], [0, 1, 0]),
+];
+
+// each face is two triangles
+const FACE_VERTICES: usize = 6;
+
+// One section's faces, split by whether they're blended over what's behind them.
+#[derive(Default)]
+pub struct SectionVertices {
+    pub opaque: Vec<TextureVertex>,
+    pub translucent: Vec<TextureVertex>,
+}
+
+// corner of a face left completely open
+pub const UNOCCLUDED: u8 = 3;
+
+// How open a face corner is, from the two blocks beside it and the one diagonally across,
+// all in the layer in front of the face. Two sides are enough to box the corner in even
+// without the diagonal.
+pub fn corner_occlusion(side: bool, other_side: bool, corner: bool) -> u8 {
+    if side && other_side {
+        return 0;
+    }
+    UNOCCLUDED - side as u8 - other_side as u8 - corner as u8
+}
+
+// occlusion at the four corners of one face of a block, in FACES order
+pub fn face_occlusion<C: Cells>(cells: &C, block: &IVec3, face: usize) -> [u8; 4] {
+    let (normal, corners) = &FACES[face];
+    let normal = vec3(normal[0], normal[1], normal[2]);
+    let opaque = |offset: IVec3| cells.block(&(block + normal + offset)).map_or(false, |b| !b.is_transparent());
+    let mut occlusion = [UNOCCLUDED; 4];
+    for (i, corner) in corners.iter().enumerate() {
+        // the corner's offset along the face, split into its two directions
+        let along = vec3(corner[0], corner[1], corner[2]) - normal;
+        let axis = (0..3).filter(|a| along[*a] != 0).collect::<Vec<usize>>();
+        let mut side = vec3(0, 0, 0);
+        side[axis[0]] = along[axis[0]];
+        let mut other_side = vec3(0, 0, 0);
+        other_side[axis[1]] = along[axis[1]];
+        occlusion[i] = corner_occlusion(opaque(side), opaque(other_side), opaque(along));
+    }
+    occlusion
+}
+
+// Quads are split along the diagonal whose corners are more open, otherwise occlusion
+// interpolated across the two triangles shows up as a crease that turns with the face.
+pub fn flipped(occlusion: &[u8; 4]) -> bool {
+    occlusion[1] + occlusion[3] > occlusion[0] + occlusion[2]
+}
+
+// Adds the faces of a block that can be seen, each lit by the cell in front of it and
+// occluded at its corners. Faces against opaque blocks or the same liquid are left out. Faces
+// with a texture in the atlas show it, the rest keep the block's colour on the blank tile.
+pub fn block_faces<C: Cells>(cells: &C, atlas: &Atlas, block: &IVec3, block_type: BlockType, vertices: &mut Vec<TextureVertex>) {
+    let (r, g, b, a) = block_type.color();
+    let centre = vec3(block.x as f32, block.y as f32, block.z as f32);
+    for (face, (normal, corners)) in FACES.iter().enumerate() {
+        let front = block + vec3(normal[0], normal[1], normal[2]);
+        let hidden = match cells.block(&front) {
+            Some(other) => !other.is_transparent() || (other.is_liquid() && other == block_type),
+            None => false,
+        };
+        if hidden {
+            continue;
+        }
+
+        let light = [light::brightness(cells.light(&front, Channel::Sky)), light::brightness(cells.light(&front, Channel::Block))];
+        let normal = [normal[0] as f32, normal[1] as f32, normal[2] as f32];
+        let occlusion = face_occlusion(cells, block, face);
+        let (tile, color) = match atlas.tile(block_type, Face::of_normal(&FACES[face].0)) {
+            Some(tile) => (tile, [1., 1., 1., a]),
+            None => (atlas.blank_tile(), [r, g, b, a]),
+        };
+        let (across, up) = TEXTURE_AXES[face];
+        let vertex = |i: usize| {
+            let corner = corners[i];
+            let position = centre + vec3(corner[0] as f32, corner[1] as f32, corner[2] as f32).scale(0.5);
+            // how far along the tile the corner is, 0 or 1 each way
+            let along = |axis: [i32; 3]| (1 + (0..3).map(|a| axis[a] * corner[a]).sum::<i32>()) as f32 / 2.;
+            let texture = [
+                tile.min[0] + (tile.max[0] - tile.min[0]) * along(across),
+                tile.min[1] + (tile.max[1] - tile.min[1]) * along(up),
+            ];
+            TextureVertex::new(position.into(), normal, texture, color, light, occlusion[i] as f32 / UNOCCLUDED as f32)
+        };
+        let order = if flipped(&occlusion) { [1, 2, 3, 3, 0, 1] } else { [0, 1, 2, 2, 3, 0] };
+        vertices.extend(order.iter().map(|i| vertex(*i)));
+    }
+}
+
+// Orders whole faces furthest from the eye first, so blending each one over those already
+// drawn comes out right. The first and third vertices of a face are opposite corners either
+// way its quad is split, so its centre is halfway between them.
+pub fn sort_back_to_front(vertices: &mut Vec<TextureVertex>, eye: &Vec3) {
+    let distance = |face: &[TextureVertex]| {
+        let centre = (Vec3::from(face[0].position) + Vec3::from(face[2].position)) / 2.;
+        glm::distance2(&centre, eye)
+    };
+    let mut faces: Vec<(f32, &[TextureVertex])> = vertices.chunks(FACE_VERTICES).map(|face| (distance(face), face)).collect();
+    faces.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    *vertices = faces.into_iter().flat_map(|(_, face)| face.iter().copied()).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::chunk::Chunk;
+    use nalgebra::Point2;
+
+    const TOP: usize = 2;
+
+    // a stone floor across the chunk at local height 0
+    fn floor() -> Chunk {
+        let mut chunk = Chunk::new(Point2::new(0, 0));
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.add_block([x, 0, z].into(), BlockType::STONE);
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn test_corner_occlusion() {
+        assert_eq!(corner_occlusion(false, false, false), 3);
+        assert_eq!(corner_occlusion(false, false, true), 2);
+        assert_eq!(corner_occlusion(true, false, true), 1);
+        assert_eq!(corner_occlusion(true, true, false), 0);
+    }
+
+    #[test]
+    fn test_open_floor_is_unoccluded() {
+        let chunk = floor();
+        assert_eq!(face_occlusion(&chunk, &vec3(5, 0, 5), TOP), [3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_wall_darkens_the_corners_next_to_it() {
+        let mut chunk = floor();
+        // a wall running along z just past the block's +x side
+        for z in 3..8 {
+            chunk.add_block([6, 1, z].into(), BlockType::STONE);
+        }
+        // the top face's corners are (-x, +z), (+x, +z), (+x, -z), (-x, -z)
+        assert_eq!(face_occlusion(&chunk, &vec3(5, 0, 5), TOP), [3, 1, 1, 3]);
+        // one block further along the wall ends, leaving only the diagonal
+        assert_eq!(face_occlusion(&chunk, &vec3(5, 0, 8), TOP), [3, 3, 2, 3]);
+    }
+
+    #[test]
+    fn test_inside_corner_is_boxed_in() {
+        let mut chunk = floor();
+        chunk.add_block([6, 1, 5].into(), BlockType::STONE);
+        chunk.add_block([5, 1, 6].into(), BlockType::STONE);
+        assert_eq!(face_occlusion(&chunk, &vec3(5, 0, 5), TOP)[1], 0);
+    }
+
+    #[test]
+    fn test_water_does_not_occlude() {
+        let mut chunk = floor();
+        chunk.add_block([6, 1, 5].into(), BlockType::WATER);
+        chunk.add_block([5, 1, 6].into(), BlockType::WATER);
+        assert_eq!(face_occlusion(&chunk, &vec3(5, 0, 5), TOP), [3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_quads_split_along_the_more_open_diagonal() {
+        assert!(!flipped(&[3, 3, 3, 3]));
+        assert!(flipped(&[0, 3, 3, 3]));
+        assert!(!flipped(&[3, 0, 3, 3]));
+    }
+
+    #[test]
+    fn test_hidden_faces_are_skipped() {
+        let mut chunk = floor();
+        let mut vertices = Vec::new();
+        block_faces(&chunk, &Atlas::blank(), &vec3(5, 0, 5), BlockType::STONE, &mut vertices);
+        assert_eq!(vertices.len(), 12, "only the top, and the bottom over the open space below");
+
+        // water shows through to the stone under it, but not to more water
+        chunk.add_block([5, 1, 5].into(), BlockType::WATER);
+        chunk.add_block([6, 1, 5].into(), BlockType::WATER);
+        vertices.clear();
+        block_faces(&chunk, &Atlas::blank(), &vec3(5, 0, 5), BlockType::STONE, &mut vertices);
+        assert_eq!(vertices.len(), 12);
+        vertices.clear();
+        block_faces(&chunk, &Atlas::blank(), &vec3(5, 1, 5), BlockType::WATER, &mut vertices);
+        assert_eq!(vertices.len(), 6 * 4);
+    }
+
+    #[test]
+    fn test_occluded_corners_reach_the_vertices() {
+        let mut chunk = floor();
+        chunk.add_block([6, 1, 5].into(), BlockType::STONE);
+        chunk.add_block([5, 1, 6].into(), BlockType::STONE);
+        let mut vertices = Vec::new();
+        block_faces(&chunk, &Atlas::blank(), &vec3(5, 0, 5), BlockType::STONE, &mut vertices);
+        let corner = vertices.iter().find(|v| v.normal == [0., 1., 0.] && v.position == [5.5, 0.5, 5.5]).unwrap();
+        assert_eq!(corner.occlusion, 0.);
+        let open = vertices.iter().find(|v| v.normal == [0., 1., 0.] && v.position == [4.5, 0.5, 4.5]).unwrap();
+        assert_eq!(open.occlusion, 1.);
+    }
+
+    #[test]
+    fn test_textured_faces_cover_their_tile() {
+        let grass = image::RgbaImage::from_pixel(16, 16, image::Rgba([0, 200, 0, 255]));
+        let atlas = Atlas::pack(vec![((BlockType::STONE, Face::Top), grass)]);
+        let mut vertices = Vec::new();
+        block_faces(&floor(), &atlas, &vec3(5, 0, 5), BlockType::STONE, &mut vertices);
+        let top: Vec<&TextureVertex> = vertices.iter().filter(|v| v.normal == [0., 1., 0.]).collect();
+        let tile = atlas.tile(BlockType::STONE, Face::Top).unwrap();
+        assert!(top.iter().all(|v| v.color == [1., 1., 1., 1.]));
+        assert!(top.iter().any(|v| v.texture == tile.min));
+        assert!(top.iter().any(|v| v.texture == tile.max));
+
+        // the bottom has no texture of its own and keeps the stone's colour
+        let (r, g, b, a) = BlockType::STONE.color();
+        let bottom = vertices.iter().find(|v| v.normal == [0., -1., 0.]).unwrap();
+        assert_eq!(bottom.color, [r, g, b, a]);
+        let blank = atlas.blank_tile();
+        assert!((0..2).all(|a| bottom.texture[a] >= blank.min[a] && bottom.texture[a] <= blank.max[a]));
+    }
+
+    #[test]
+    fn test_side_textures_are_upright() {
+        let side = image::RgbaImage::from_pixel(16, 16, image::Rgba([0, 0, 0, 255]));
+        let atlas = Atlas::pack(vec![((BlockType::STONE, Face::Side), side)]);
+        let tile = atlas.tile(BlockType::STONE, Face::Side).unwrap();
+        let mut vertices = Vec::new();
+        block_faces(&Chunk::new(Point2::new(0, 0)), &atlas, &vec3(5, 5, 5), BlockType::STONE, &mut vertices);
+        for v in vertices.iter().filter(|v| v.normal[1] == 0.) {
+            let top = v.position[1] > 5.;
+            assert_eq!(v.texture[1], if top { tile.max[1] } else { tile.min[1] });
+        }
+    }
+
+    #[test]
+    fn test_sorts_faces_back_to_front() {
+        let mut chunk = Chunk::new(Point2::new(0, 0));
+        for x in 0..4 {
+            chunk.add_block([x * 2, 0, 0].into(), BlockType::WATER);
+        }
+        let mut vertices = Vec::new();
+        for x in 0..4 {
+            block_faces(&chunk, &Atlas::blank(), &vec3(x * 2, 0, 0), BlockType::WATER, &mut vertices);
+        }
+        let eye = vec3(-5., 0.2, 0.3);
+        sort_back_to_front(&mut vertices, &eye);
+
+        let faces: Vec<&[TextureVertex]> = vertices.chunks(FACE_VERTICES).collect();
+        assert_eq!(faces.len(), 4 * 6);
+        let distances: Vec<f32> = faces
+            .iter()
+            .map(|face| glm::distance2(&((Vec3::from(face[0].position) + Vec3::from(face[2].position)) / 2.), &eye))
+            .collect();
+        assert!(distances.windows(2).all(|pair| pair[0] >= pair[1]));
+        // faces stay whole, all six vertices share a normal
+        assert!(faces.iter().all(|face| face.iter().all(|v| v.normal == face[0].normal)));
+        // the far side of the furthest block is first and the near side of the nearest last
+        assert_eq!(faces[0][0].normal, [1., 0., 0.]);
+        assert_eq!(faces[faces.len() - 1][0].normal, [-1., 0., 0.]);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::engine::block::BlockType;
+use glm::IVec3;
+
+// Break progress on the block under the crosshair while the mouse button is held.
+// Progress runs from 0 to 1 and starts over whenever the target changes. Every block takes
+// its hardness in seconds, there are no tools yet to speed that up.
+pub struct Mining {
+    target: Option<IVec3>,
+    progress: f32,
+}
+
+impl Mining {
+    pub fn new() -> Mining {
+        Mining {
+            target: None,
+            progress: 0.,
+        }
+    }
+
+    // the block currently being broken and how far along it is, for the HUD / crack overlay
+    pub fn target(&self) -> Option<(IVec3, f32)> {
+        self.target.map(|block| (block, self.progress))
+    }
+
+    pub fn reset(&mut self) {
+        self.target = None;
+        self.progress = 0.;
+    }
+
+    // advances by dt seconds, returns the block once it breaks
+    pub fn update(&mut self, target: Option<(IVec3, BlockType)>, dt: f32) -> Option<IVec3> {
+        let (block, block_type) = match target {
+            Some(target) => target,
+            None => {
+                self.reset();
+                return None;
+            }
+        };
+
+        if self.target != Some(block) {
+            self.target = Some(block);
+            self.progress = 0.;
+        }
+
+        let hardness = block_type.hardness()?;
+        self.progress += if hardness > 0. { dt / hardness } else { 1. };
+        if self.progress >= 1. {
+            self.reset();
+            Some(block)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::vec3;
+
+    #[test]
+    fn test_breaks_after_hardness() {
+        let mut mining = Mining::new();
+        let block = vec3(1, 2, 3);
+        let hardness = BlockType::STONE.hardness().unwrap();
+        let mut ticks = 1;
+        while mining.update(Some((block, BlockType::STONE)), 0.1).is_none() {
+            ticks += 1;
+        }
+        // allow one tick of floating point slack
+        assert!((ticks as f32 - hardness * 10.).abs() <= 1.);
+        assert!(mining.target().is_none());
+    }
+
+    #[test]
+    fn test_changing_target_resets_progress() {
+        let mut mining = Mining::new();
+        mining.update(Some((vec3(0, 0, 0), BlockType::STONE)), 1.);
+        assert!(mining.target().unwrap().1 > 0.);
+        mining.update(Some((vec3(1, 0, 0), BlockType::STONE)), 0.);
+        assert_eq!(mining.target(), Some((vec3(1, 0, 0), 0.)));
+    }
+
+    #[test]
+    fn test_unbreakable_blocks_never_break() {
+        let mut mining = Mining::new();
+        assert_eq!(mining.update(Some((vec3(0, 0, 0), BlockType::WATER)), 1000.), None);
+    }
+}
@@ -0,0 +1,653 @@
+use crate::config::RENDER_DISTANCE;
+use crate::engine::block::BlockType;
+use crate::engine::chunk::CHUNK_SIZE;
+use crate::engine::entity::{Appearance, Body, Entities, EntityId, Mob, Transform};
+use crate::engine::generator::Biome;
+use crate::engine::health::{DamageKind, Health, Resistances};
+use crate::engine::light::{self, MAX_LIGHT};
+use crate::engine::pathfinding::{self, Agent, Path};
+use crate::engine::world::{World, UNLOAD_MARGIN};
+use glm::{vec3, IVec3, Vec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+const GRAVITY: f32 = 20.0;
+const GROUND_FRICTION: f32 = 8.0;
+const JUMP_SPEED: f32 = 7.5;
+const SPAWN_INTERVAL: f32 = 1.0; // seconds between natural spawn attempts
+const GROUP_SPREAD: i32 = 2; // blocks a group may be spread around its first member
+const WANDER_RADIUS: i32 = 6;
+const FLEE_SPEEDUP: f32 = 1.5;
+const FOLLOW_DISTANCE: f32 = 2.5;
+const ATTACK_REACH: f32 = 1.0; // beyond the mob's own half size
+const ATTACK_COOLDOWN: f32 = 1.0;
+const REPATH_INTERVAL: f32 = 1.0;
+const PATH_NODES: usize = 256;
+const WAYPOINT_REACHED: f32 = 0.3;
+
+// What a mob does, checked in the order of its definition. Ranges are blocks from the player.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Behaviour {
+    Wander,
+    Flee(f32),
+    Follow(f32),
+    Attack(f32),
+}
+
+impl Behaviour {
+    fn applies(&self, distance: f32) -> bool {
+        match *self {
+            Behaviour::Wander => true,
+            Behaviour::Flee(range) | Behaviour::Follow(range) | Behaviour::Attack(range) => distance <= range,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnRule {
+    pub on: Vec<BlockType>, // never spawns naturally when empty
+    pub light: (u8, u8),
+    pub biomes: Option<Vec<Biome>>, // None for any biome
+    pub distance: (f32, f32),
+    pub group: (u32, u32),
+    pub cap: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MobDefinition {
+    pub id: String,
+    pub name: String,
+    pub color: (f32, f32, f32),
+    pub size: f32,
+    pub speed: f32,
+    pub health: f32,
+    pub resistances: Resistances,
+    pub damage: f32,
+    pub spawn: SpawnRule,
+    pub behaviours: Vec<Behaviour>,
+}
+
+impl MobDefinition {
+    fn new(id: &str) -> MobDefinition {
+        MobDefinition {
+            id: id.to_string(),
+            name: id.to_string(),
+            color: (1., 1., 1.),
+            size: 0.8,
+            speed: 2.0,
+            health: 10.,
+            resistances: Resistances::default(),
+            damage: 0.,
+            spawn: SpawnRule {
+                on: Vec::new(),
+                light: (0, MAX_LIGHT),
+                biomes: None,
+                distance: (24., 64.),
+                group: (1, 1),
+                cap: 4,
+            },
+            behaviours: Vec::new(),
+        }
+    }
+
+    // every definition in a file laid out like data/mobs.txt
+    pub fn parse_all(text: &str) -> Result<Vec<MobDefinition>, String> {
+        let mut definitions: Vec<MobDefinition> = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split('#').next().unwrap().split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {}", n + 1, message);
+            if fields[0] == "mob" {
+                match fields[1..] {
+                    [id] => definitions.push(MobDefinition::new(id)),
+                    _ => return Err(error("expected mob <id>".to_string())),
+                }
+                continue;
+            }
+            match definitions.last_mut() {
+                Some(definition) => definition.set(fields[0], &fields[1..]).map_err(error)?,
+                None => return Err(error(format!("{} before the first mob", fields[0]))),
+            }
+        }
+        Ok(definitions)
+    }
+
+    fn set(&mut self, field: &str, args: &[&str]) -> Result<(), String> {
+        match field {
+            "name" => self.name = args.join(" "),
+            "color" => {
+                let c: Vec<f32> = numbers(args, 3)?;
+                self.color = (c[0], c[1], c[2]);
+            }
+            "size" => self.size = numbers(args, 1)?[0],
+            "speed" => self.speed = numbers(args, 1)?[0],
+            "health" => self.health = numbers(args, 1)?[0],
+            "resist" => match args {
+                [kind, fraction] => {
+                    let kind = DamageKind::from_id(kind).ok_or(format!("unknown damage {}", kind))?;
+                    self.resistances.set(kind, numbers(&[fraction], 1)?[0]);
+                }
+                _ => return Err("expected resist <damage> <fraction>".to_string()),
+            },
+            "damage" => self.damage = numbers(args, 1)?[0],
+            "spawn_on" => {
+                self.spawn.on = args
+                    .iter()
+                    .map(|id| BlockType::from_id(id).ok_or(format!("unknown block {}", id)))
+                    .collect::<Result<_, _>>()?;
+            }
+            "light" => {
+                let l: Vec<u8> = numbers(args, 2)?;
+                self.spawn.light = (l[0], l[1]);
+            }
+            "biomes" if args == ["any"] => self.spawn.biomes = None,
+            "biomes" => {
+                let biomes = args
+                    .iter()
+                    .map(|id| Biome::from_id(id).ok_or(format!("unknown biome {}", id)))
+                    .collect::<Result<_, _>>()?;
+                self.spawn.biomes = Some(biomes);
+            }
+            "distance" => {
+                let d: Vec<f32> = numbers(args, 2)?;
+                self.spawn.distance = (d[0], d[1]);
+            }
+            "group" => {
+                let g: Vec<u32> = numbers(args, 2)?;
+                self.spawn.group = (g[0], g[1].max(g[0]));
+            }
+            "cap" => self.spawn.cap = numbers(args, 1)?[0],
+            "behaviour" => {
+                let behaviour = match args {
+                    ["wander"] => Behaviour::Wander,
+                    ["flee", range] => Behaviour::Flee(numbers(&[range], 1)?[0]),
+                    ["follow", range] => Behaviour::Follow(numbers(&[range], 1)?[0]),
+                    ["attack", range] => Behaviour::Attack(numbers(&[range], 1)?[0]),
+                    _ => return Err(format!("unknown behaviour {}", args.join(" "))),
+                };
+                self.behaviours.push(behaviour);
+            }
+            _ => return Err(format!("unknown field {}", field)),
+        }
+        Ok(())
+    }
+
+    fn agent(&self) -> Agent {
+        Agent {
+            height: self.size.ceil() as i32,
+            step: 1,
+            drop: 3,
+        }
+    }
+}
+
+fn numbers<T: FromStr>(args: &[&str], count: usize) -> Result<Vec<T>, String> {
+    if args.len() != count {
+        return Err(format!("expected {} values, got {}", count, args.len()));
+    }
+    args.iter().map(|a| a.parse().map_err(|_| format!("bad value {}", a))).collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MobAttack {
+    pub mob: EntityId,
+    pub damage: f32,
+}
+
+// AI state that isn't worth saving, a reloaded mob starts over with a fresh one
+#[derive(Default)]
+struct Brain {
+    goal: Option<Vec3>, // where wandering is headed
+    timer: f32, // seconds until wandering picks a new goal
+    cooldown: f32, // seconds until the next attack
+    path: Option<Path>,
+    path_age: f32,
+}
+
+// Spawning, despawning and AI for every creature in the world. Mobs are ordinary entities
+// with a Mob component, so they fall with entity physics and are saved with their chunk.
+pub struct Mobs {
+    definitions: Vec<MobDefinition>,
+    brains: HashMap<EntityId, Brain>,
+    rng: StdRng,
+    spawn_timer: f32,
+}
+
+impl Mobs {
+    pub fn new(definitions: Vec<MobDefinition>, seed: u64) -> Mobs {
+        Mobs {
+            definitions,
+            brains: HashMap::new(),
+            rng: StdRng::seed_from_u64(seed),
+            spawn_timer: 0.,
+        }
+    }
+
+    // the definitions shipped in data/mobs.txt, as they were when the game was built
+    pub fn builtin() -> Vec<MobDefinition> {
+        MobDefinition::parse_all(include_str!("../../data/mobs.txt")).expect("to parse data/mobs.txt")
+    }
+
+    // reads definitions at startup so they can be edited without a rebuild, falling back to the
+    // built in ones when the file isn't there
+    pub fn load(file: &std::path::Path) -> Result<Vec<MobDefinition>, String> {
+        match fs::read_to_string(file) {
+            Ok(text) => MobDefinition::parse_all(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Mobs::builtin()),
+            Err(e) => Err(format!("{}: {}", file.display(), e)),
+        }
+    }
+
+    pub fn definition(&self, kind: &str) -> Option<&MobDefinition> {
+        self.definitions.iter().find(|d| d.id == kind)
+    }
+
+    // places a mob standing at feet
+    pub fn spawn(&self, entities: &mut Entities, kind: &str, feet: Vec3) -> Option<EntityId> {
+        let definition = self.definition(kind)?;
+        let id = entities.spawn(Transform {
+            position: feet + vec3(0., definition.size / 2., 0.),
+            velocity: vec3(0., 0., 0.),
+        });
+        entities.bodies.insert(id, Body {
+            size: definition.size,
+            gravity: GRAVITY,
+            friction: GROUND_FRICTION,
+            on_ground: false,
+            impact: 0.,
+        });
+        let (r, g, b) = definition.color;
+        entities.appearances.insert(id, Appearance {
+            color: (r, g, b, 1.),
+            scale: definition.size,
+        });
+        entities.mobs.insert(id, Mob { kind: kind.to_string() });
+        entities.healths.insert(id, Health {
+            resistances: definition.resistances,
+            ..Health::new(definition.health)
+        });
+        Some(id)
+    }
+
+    // whether the spawn rules allow a mob to appear standing in the block at feet
+    pub fn can_spawn(world: &World, definition: &MobDefinition, feet: &IVec3, player: &Vec3) -> bool {
+        let rule = &definition.spawn;
+        let distance = glm::distance(&glm::convert(*feet), player);
+        // light levels are compared as brightness so moonlight counts as dark
+        let brightness = world.brightness(feet);
+        world.get_block(&(feet - vec3(0, 1, 0))).map_or(false, |b| rule.on.contains(b))
+            && distance >= rule.distance.0
+            && distance <= rule.distance.1
+            && brightness >= light::brightness(rule.light.0)
+            && brightness <= light::brightness(rule.light.1)
+            && rule.biomes.as_ref().map_or(true, |biomes| biomes.contains(&world.biome(feet)))
+            && definition.agent().can_stand(world, feet)
+    }
+
+    // runs once per simulation tick before the world moves entities, player is at their feet
+    pub fn update(&mut self, world: &mut World, player: &Vec3, dt: f32) -> Vec<MobAttack> {
+        self.despawn_far(world.entities_mut(), player);
+        self.spawn_timer -= dt;
+        if self.spawn_timer <= 0. {
+            self.spawn_timer += SPAWN_INTERVAL;
+            self.spawn_natural(world, player);
+        }
+        self.think(world, player, dt)
+    }
+
+    fn count(entities: &Entities, kind: &str) -> usize {
+        entities.mobs.values().filter(|m| m.kind == kind).count()
+    }
+
+    // mobs are dropped rather than saved once they leave the chunks kept around the player
+    fn despawn_far(&mut self, entities: &mut Entities, player: &Vec3) {
+        let center = World::locate(&World::block_at(player)).0;
+        let keep = RENDER_DISTANCE + UNLOAD_MARGIN;
+        let far: Vec<EntityId> = entities
+            .mobs
+            .keys()
+            .filter(|id| {
+                let chunk = World::locate(&World::block_at(&entities.transforms[id].position)).0;
+                (chunk[0] - center[0]).abs() > keep || (chunk[1] - center[1]).abs() > keep
+            })
+            .copied()
+            .collect();
+        for id in far {
+            entities.despawn(id);
+        }
+        self.brains.retain(|id, _| entities.mobs.contains_key(id));
+    }
+
+    // one attempt per definition: a random column in the loaded radius, then the rest of the group around it
+    fn spawn_natural(&mut self, world: &mut World, player: &Vec3) {
+        let center = World::locate(&World::block_at(player)).0;
+        for n in 0..self.definitions.len() {
+            let definition = &self.definitions[n];
+            let mut room = definition.spawn.cap.saturating_sub(Self::count(world.entities(), &definition.id));
+            if room == 0 {
+                continue;
+            }
+
+            let x = (center[0] + self.rng.gen_range(-RENDER_DISTANCE, RENDER_DISTANCE + 1)) * CHUNK_SIZE
+                + self.rng.gen_range(0, CHUNK_SIZE);
+            let z = (center[1] + self.rng.gen_range(-RENDER_DISTANCE, RENDER_DISTANCE + 1)) * CHUNK_SIZE
+                + self.rng.gen_range(0, CHUNK_SIZE);
+            let group = self.rng.gen_range(definition.spawn.group.0, definition.spawn.group.1 + 1);
+
+            let mut spawned = Vec::new();
+            for member in 0..group {
+                let (dx, dz) = match member {
+                    0 => (0, 0),
+                    _ => (self.rng.gen_range(-GROUP_SPREAD, GROUP_SPREAD + 1), self.rng.gen_range(-GROUP_SPREAD, GROUP_SPREAD + 1)),
+                };
+                let feet = match world.surface(x + dx, z + dz) {
+                    Some(surface) => surface + vec3(0, 1, 0),
+                    None => continue,
+                };
+                if room > 0 && !spawned.contains(&feet) && Self::can_spawn(world, definition, &feet, player) {
+                    spawned.push(feet);
+                    room -= 1;
+                } else if member == 0 {
+                    break;
+                }
+            }
+
+            let kind = definition.id.clone();
+            for feet in spawned {
+                let position = vec3(feet.x as f32, feet.y as f32 - 0.5, feet.z as f32);
+                self.spawn(world.entities_mut(), &kind, position);
+            }
+        }
+    }
+
+    // picks a behaviour for every mob and turns it into a walking velocity
+    fn think(&mut self, world: &mut World, player: &Vec3, dt: f32) -> Vec<MobAttack> {
+        let mut moves = Vec::new();
+        let mut attacks = Vec::new();
+        let entities = world.entities();
+        let ids: Vec<EntityId> = entities.ids().into_iter().filter(|id| entities.mobs.contains_key(id)).collect();
+        for id in ids {
+            // mobs from saves whose definition has since been removed just stand still
+            let definition = match self.definitions.iter().find(|d| d.id == entities.mobs[&id].kind) {
+                Some(definition) => definition,
+                None => continue,
+            };
+            let feet = entities.transforms[&id].position - vec3(0., definition.size / 2., 0.);
+            let on_ground = entities.bodies.get(&id).map_or(false, |b| b.on_ground);
+            let brain = self.brains.entry(id).or_default();
+            brain.cooldown -= dt;
+            brain.path_age += dt;
+
+            let distance = glm::distance(&feet, player);
+            let (target, speed) = match definition.behaviours.iter().find(|b| b.applies(distance)) {
+                Some(Behaviour::Flee(_)) => {
+                    brain.path = None;
+                    (Some(feet + (feet - player)), definition.speed * FLEE_SPEEDUP)
+                }
+                Some(Behaviour::Follow(_)) if distance > FOLLOW_DISTANCE => {
+                    (Some(Self::chase(world, definition, brain, &feet, player)), definition.speed)
+                }
+                Some(Behaviour::Attack(_)) if distance > definition.size / 2. + ATTACK_REACH => {
+                    (Some(Self::chase(world, definition, brain, &feet, player)), definition.speed)
+                }
+                Some(Behaviour::Attack(_)) => {
+                    if brain.cooldown <= 0. {
+                        brain.cooldown = ATTACK_COOLDOWN;
+                        attacks.push(MobAttack { mob: id, damage: definition.damage });
+                    }
+                    (None, 0.)
+                }
+                Some(Behaviour::Follow(_)) => (None, 0.),
+                Some(Behaviour::Wander) | None => {
+                    brain.path = None;
+                    (Self::wander(world, brain, &mut self.rng, &feet, dt), definition.speed)
+                }
+            };
+
+            let mut direction = target.map_or(vec3(0., 0., 0.), |target| vec3(target.x - feet.x, 0., target.z - feet.z));
+            if glm::length(&direction) > WAYPOINT_REACHED {
+                direction = glm::normalize(&direction);
+            } else {
+                direction = vec3(0., 0., 0.);
+            }
+            let jump = on_ground && Self::blocked(world, definition, &feet, &direction);
+            moves.push((id, direction * speed, jump));
+        }
+
+        let entities = world.entities_mut();
+        for (id, velocity, jump) in moves {
+            if let Some(transform) = entities.transforms.get_mut(&id) {
+                transform.velocity.x = velocity.x;
+                transform.velocity.z = velocity.z;
+                if jump {
+                    transform.velocity.y = JUMP_SPEED;
+                }
+            }
+        }
+        attacks
+    }
+
+    // next point to walk towards on the way to the player, straight at them when there is no path
+    fn chase(world: &World, definition: &MobDefinition, brain: &mut Brain, feet: &Vec3, player: &Vec3) -> Vec3 {
+        let stale = brain.path_age >= REPATH_INTERVAL || brain.path.as_mut().map_or(true, |p| !p.is_valid(world));
+        if stale {
+            let start = World::block_at(&(feet + vec3(0., 0.01, 0.)));
+            let goal = World::block_at(&(player + vec3(0., 0.01, 0.)));
+            brain.path = pathfinding::find_path(world, definition.agent(), start, goal, PATH_NODES);
+            brain.path_age = 0.;
+        }
+        let path = match brain.path.as_mut() {
+            Some(path) => path,
+            None => return *player,
+        };
+        while let Some(waypoint) = path.waypoints.first() {
+            let reached = glm::distance(&vec3(waypoint.x as f32, 0., waypoint.z as f32), &vec3(feet.x, 0., feet.z));
+            if reached < WAYPOINT_REACHED {
+                path.waypoints.remove(0);
+            } else {
+                break;
+            }
+        }
+        path.waypoints.first().map_or(*player, |w| vec3(w.x as f32, w.y as f32 - 0.5, w.z as f32))
+    }
+
+    // strolls to a random nearby column every few seconds, with pauses in between
+    fn wander(world: &World, brain: &mut Brain, rng: &mut StdRng, feet: &Vec3, dt: f32) -> Option<Vec3> {
+        brain.timer -= dt;
+        if brain.timer <= 0. {
+            brain.timer = rng.gen_range(3., 8.);
+            brain.goal = None;
+            if rng.gen_bool(0.6) {
+                let x = feet.x.round() as i32 + rng.gen_range(-WANDER_RADIUS, WANDER_RADIUS + 1);
+                let z = feet.z.round() as i32 + rng.gen_range(-WANDER_RADIUS, WANDER_RADIUS + 1);
+                brain.goal = world.surface(x, z).map(|s| vec3(s.x as f32, s.y as f32 + 0.5, s.z as f32));
+            }
+        }
+        brain.goal
+    }
+
+    // a one block ledge straight ahead with room to jump onto it
+    fn blocked(world: &World, definition: &MobDefinition, feet: &Vec3, direction: &Vec3) -> bool {
+        if glm::length(direction) == 0. {
+            return false;
+        }
+        let ahead = World::block_at(&(feet + direction * (definition.size / 2. + 0.2) + vec3(0., 0.01, 0.)));
+        world.is_solid(&ahead) && !world.is_solid(&(ahead + vec3(0, 1, 0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::clock::{MIDNIGHT, NOON};
+
+    const TEST_MOBS: &str = "
+        mob walker
+        size 0.8
+        speed 3
+        damage 2
+        spawn_on grass dirt sand stone
+        distance 4 40
+        group 1 2
+        cap 3
+        behaviour attack 16
+        behaviour wander
+
+        mob shy
+        spawn_on grass
+        light 8 15
+        distance 4 40
+        behaviour flee 6
+    ";
+
+    fn mobs() -> Mobs {
+        Mobs::new(MobDefinition::parse_all(TEST_MOBS).unwrap(), 1)
+    }
+
+    fn run(mobs: &mut Mobs, world: &mut World, player: &Vec3, seconds: f32) -> Vec<MobAttack> {
+        let mut attacks = Vec::new();
+        for _ in 0..(seconds * 60.) as usize {
+            attacks.extend(mobs.update(world, player, 1. / 60.));
+            world.tick(1. / 60.);
+        }
+        attacks
+    }
+
+    fn feet(world: &World, id: EntityId) -> Vec3 {
+        world.entities().transforms[&id].position - vec3(0., 0.4, 0.)
+    }
+
+    #[test]
+    fn test_builtin_definitions() {
+        let definitions = Mobs::builtin();
+        let pig = definitions.iter().find(|d| d.id == "pig").unwrap();
+        assert_eq!(pig.spawn.on, vec![BlockType::GRASS]);
+        assert_eq!(pig.spawn.biomes, Some(vec![Biome::Plains, Biome::Forest]));
+        assert_eq!(pig.behaviours, vec![Behaviour::Flee(4.), Behaviour::Wander]);
+        let zombie = definitions.iter().find(|d| d.id == "zombie").unwrap();
+        assert_eq!(zombie.resistances.fall, 0.5);
+    }
+
+    #[test]
+    fn test_loads_the_data_file_or_falls_back() {
+        let file = std::env::temp_dir().join(format!("boxel-mobs-{}.txt", std::process::id()));
+        assert_eq!(Mobs::load(&file).unwrap().len(), Mobs::builtin().len());
+
+        fs::write(&file, "mob slime\nspawn_on grass").unwrap();
+        let loaded = Mobs::load(&file);
+        fs::remove_file(&file).unwrap();
+        let definitions = loaded.unwrap();
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].id, "slime");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(MobDefinition::parse_all("size 1").unwrap_err(), "line 1: size before the first mob");
+        assert_eq!(MobDefinition::parse_all("mob a\n\nspawn_on lava").unwrap_err(), "line 3: unknown block lava");
+        assert_eq!(MobDefinition::parse_all("mob a\nlight 3").unwrap_err(), "line 2: expected 2 values, got 1");
+        assert!(MobDefinition::parse_all("mob a\nbehaviour dance").is_err());
+    }
+
+    #[test]
+    fn test_spawn_rules() {
+        let mut world = World::flat(1, false);
+        let mobs = mobs();
+        let shy = mobs.definition("shy").unwrap();
+        let player = vec3(0., 16.5, 0.);
+
+        // x + z = 3 is grass, 4 is dirt
+        assert!(Mobs::can_spawn(&world, shy, &vec3(10, 17, 9), &player));
+        assert!(!Mobs::can_spawn(&world, shy, &vec3(10, 17, 10), &player), "wrong block");
+        assert!(!Mobs::can_spawn(&world, shy, &vec3(2, 17, 1), &player), "too close");
+        assert!(!Mobs::can_spawn(&world, shy, &vec3(10, 18, 9), &player), "floating");
+
+        // walled in and roofed over, with no light getting in
+        for y in 17..19 {
+            for (x, z) in [(9, 9), (11, 9), (10, 8), (10, 10)].iter() {
+                world.set_block(&vec3(*x, y, *z), BlockType::STONE);
+            }
+        }
+        world.set_block(&vec3(10, 19, 9), BlockType::STONE);
+        assert!(!Mobs::can_spawn(&world, shy, &vec3(10, 17, 9), &player), "too dark");
+        assert!(Mobs::can_spawn(&world, mobs.definition("walker").unwrap(), &vec3(10, 17, 9), &player));
+    }
+
+    #[test]
+    fn test_dark_rules_follow_the_time_of_day() {
+        let mut world = World::flat(1, false);
+        let definitions = MobDefinition::parse_all("mob lurker\nspawn_on grass dirt\nlight 0 7\ndistance 4 40").unwrap();
+        let player = vec3(0., 16.5, 0.);
+        let open_ground = vec3(10, 17, 9);
+
+        world.clock_mut().set_time_of_day(MIDNIGHT);
+        assert!(Mobs::can_spawn(&world, &definitions[0], &open_ground, &player));
+        world.clock_mut().set_time_of_day(NOON);
+        assert!(!Mobs::can_spawn(&world, &definitions[0], &open_ground, &player));
+    }
+
+    #[test]
+    fn test_natural_spawning_respects_caps_and_loaded_chunks() {
+        let mut world = World::flat(1, false);
+        let mut mobs = mobs();
+        run(&mut mobs, &mut world, &vec3(8., 16.5, 8.), 120.);
+
+        let entities = world.entities();
+        assert!(Mobs::count(entities, "walker") > 0);
+        assert!(Mobs::count(entities, "walker") <= 3);
+        for id in entities.mobs.keys() {
+            assert!(world.is_loaded(&World::block_at(&entities.transforms[id].position)));
+        }
+    }
+
+    #[test]
+    fn test_mobs_despawn_outside_loaded_radius() {
+        let mut world = World::flat(0, false);
+        let mut mobs = mobs();
+        let id = mobs.spawn(world.entities_mut(), "walker", vec3(8., 16.5, 8.)).unwrap();
+        mobs.update(&mut world, &vec3(8., 16.5, 8.), 1. / 60.);
+        assert!(world.entities().mobs.contains_key(&id));
+
+        // past the render distance but still inside the chunks that are kept loaded
+        let near = (RENDER_DISTANCE + 1) as f32 * CHUNK_SIZE as f32 + 8.;
+        mobs.update(&mut world, &vec3(near, 16.5, 8.), 1. / 60.);
+        assert!(world.entities().mobs.contains_key(&id));
+
+        let far = (RENDER_DISTANCE + UNLOAD_MARGIN + 1) as f32 * CHUNK_SIZE as f32 + 8.;
+        mobs.update(&mut world, &vec3(far, 16.5, 8.), 1. / 60.);
+        assert!(world.entities().mobs.is_empty());
+    }
+
+    #[test]
+    fn test_attackers_chase_around_walls() {
+        let mut world = World::flat(1, false);
+        for z in 0..12 {
+            world.set_block(&vec3(6, 17, z), BlockType::STONE);
+            world.set_block(&vec3(6, 18, z), BlockType::STONE);
+        }
+        let mut mobs = mobs();
+        let id = mobs.spawn(world.entities_mut(), "walker", vec3(2., 16.5, 4.)).unwrap();
+        let player = vec3(10., 16.5, 4.);
+
+        let attacks = run(&mut mobs, &mut world, &player, 15.);
+        assert!(glm::distance(&feet(&world, id), &player) < 1.5);
+        assert!(attacks.contains(&MobAttack { mob: id, damage: 2. }));
+    }
+
+    #[test]
+    fn test_shy_mobs_flee() {
+        let mut world = World::flat(1, false);
+        let mut mobs = mobs();
+        let id = mobs.spawn(world.entities_mut(), "shy", vec3(8., 16.5, 8.)).unwrap();
+        let player = vec3(5., 16.5, 8.);
+
+        run(&mut mobs, &mut world, &player, 1.);
+        assert!(glm::distance(&feet(&world, id), &player) > 4.);
+    }
+}
@@ -2,6 +2,8 @@ use crate::primitives::Vertex;
 use crate::camera::CameraState;
 use genmesh::{self, generators::Cube, MapToVertices, Triangulate, Vertices};
 use glium::glutin::event::VirtualKeyCode;
+use glium::texture::Texture2d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
 use glium::vertex::VertexBuffer;
 use glium::{program, uniform};
 use glium::{Display, Surface};
@@ -9,38 +11,114 @@ use glium::glutin::event;
 
 use glm::{vec3, vec4};
 
+pub mod atlas;
+mod backdrop;
 mod block;
 mod chunk;
+mod circuit;
+mod clock;
+mod commands;
+pub mod crafting;
+mod crafting_panel;
 mod crosshair;
+pub mod entity;
+mod falling;
+mod flow;
+mod frustum;
 mod march;
+mod mesh;
 pub mod generator;
-mod world;
+pub mod health;
+mod hotbar;
+pub mod inventory;
+mod item;
+pub mod light;
+mod mining;
+pub mod mob;
+pub mod pathfinding;
+mod player;
+pub mod seed;
+mod spawn;
+pub mod storage;
+pub mod ticks;
+mod timestep;
+mod visibility;
+pub mod world;
 use world::World;
+use backdrop::Backdrop;
+use commands::{Command, Console, TimeSetting};
+use crafting::{CraftingGrid, Recipes};
+use crafting_panel::CraftingPanel;
 use crosshair::Crosshair;
+use atlas::Atlas;
+use frustum::{CullStats, Frustum};
+use visibility::SECTION_SIZE;
+use entity::EntityId;
+use health::{DamageKind, HealthEvent, Target, Vitals, MAX_AIR};
+use hotbar::Hotbar;
+use inventory::{GameMode, Inventory, ItemStack, HOTBAR_SIZE};
+use item::Items;
+use mining::Mining;
+use mob::{MobDefinition, Mobs};
+use seed::{Seed, Subsystem};
+use player::{Player, EYE_HEIGHT};
+use timestep::FixedTimestep;
+use crate::config::TICKS_PER_SECOND;
 
 use std::time::Duration;
 
+// voxel steps the block selection ray may take
+const REACH: usize = 24;
+const MELEE_REACH: f32 = 3.0;
+const MELEE_DAMAGE: f32 = 2.0;
+const HURT_FLASH: f32 = 0.4; // seconds the health readout stays red after a hit
+
 pub struct Engine {
     pub camera: CameraState,
     pub display: Box<Display>,
+    player: Player,
+    spawn: glm::Vec3, // eye position the player respawns at
+    vitals: Vitals,
+    hurt_flash: f32,
+    inventory: Inventory,
+    recipes: Recipes,
+    crafting: Option<CraftingGrid>, // open while the crafting grid is shown
+    items: Items,
+    mobs: Mobs,
+    mining: Mining,
+    breaking: bool, // left mouse button held in survival
+    timestep: FixedTimestep,
     cube: VertexBuffer<Vertex>,
     program: glium::Program,
+    chunk_program: glium::Program,
+    atlas: Atlas, // where each block face's texture is, for meshing
+    atlas_texture: Texture2d,
     world: World,
     grab: bool,
     crosshair: Crosshair,
     crosshair_program: glium::Program,
+    backdrop: Backdrop,
+    sky_program: glium::Program,
+    console: Console,
+    hotbar: Hotbar,
+    crafting_panel: CraftingPanel,
     text_system: glium_text::TextSystem,
     font: glium_text::FontTexture,
 }
 
 impl Engine {
-    pub fn new(display: Display, seed: u32) -> Engine {
+    pub fn new(display: Display, mut world: World, seed: &Seed, mode: GameMode, atlas: Atlas, recipes: Recipes, mob_definitions: Vec<MobDefinition>) -> Engine {
         // the program
         let program = program!(&display,
             140 => {vertex: include_str!("./../shaders/vertex.glsl"), fragment: include_str!("./../shaders/fragment.glsl")},
         )
         .unwrap();
 
+        let chunk_program = program!(&display,
+            140 => {vertex: include_str!("./../shaders/chunk_vertex.glsl"), fragment: include_str!("./../shaders/chunk_fragment.glsl")},
+        )
+        .unwrap();
+
         let crosshair_program = program!(&display,
             140 => {vertex: include_str!("./../shaders/crosshair_vertex.glsl"), fragment: include_str!("./../shaders/crosshair_fragment.glsl")},
         )
@@ -61,11 +139,22 @@ impl Engine {
             VertexBuffer::new(&display, cube_vertices.as_slice()).unwrap()
         };
 
-        let camera = CameraState::new();
+        let spawn = world.spawn_point();
+        let eye = vec3(spawn.x as f32, spawn.y as f32 - 0.5 + EYE_HEIGHT, spawn.z as f32);
+        let mut camera = CameraState::new();
+        camera.set_position(eye);
+        let player = Player::new(&eye);
 
-        let world = World::new(seed);
+        let sky_program = program!(&display,
+            140 => {vertex: include_str!("./../shaders/sky_vertex.glsl"), fragment: include_str!("./../shaders/sky_fragment.glsl")},
+        )
+        .unwrap();
 
+        let atlas_texture = atlas.texture(&display);
         let crosshair = Crosshair::new(&display);
+        let backdrop = Backdrop::new(&display);
+        let hotbar = Hotbar::new(&display);
+        let crafting_panel = CraftingPanel::new(&display);
 
         let text_system = glium_text::TextSystem::new(&display);
         let font = glium_text::FontTexture::new(&display, &include_bytes!("../../fonts/minecraft.ttf")[..], 12).unwrap();
@@ -73,22 +162,114 @@ impl Engine {
         let display = Box::new(display);
 
         Engine {
+            spawn: eye,
             camera,
+            player,
+            vitals: Vitals::new(),
+            hurt_flash: 0.,
+            inventory: Inventory::new(mode),
+            recipes,
+            crafting: None,
+            items: Items::new(),
+            mobs: Mobs::new(mob_definitions, seed.derive(Subsystem::Mobs)),
+            mining: Mining::new(),
+            breaking: false,
+            timestep: FixedTimestep::new(TICKS_PER_SECOND),
             cube,
             display,
             program,
+            chunk_program,
+            atlas,
+            atlas_texture,
             world,
             grab: true,
             crosshair,
             crosshair_program,
+            backdrop,
+            sky_program,
+            console: Console::stdin(),
+            hotbar,
+            crafting_panel,
             text_system,
             font,
         }
     }
 
+    // runs however many fixed simulation ticks have accumulated since the last frame
+    pub fn update(&mut self, frame_time: Duration) {
+        for command in self.console.poll() {
+            match command {
+                Ok(command) => self.run_command(command),
+                Err(e) => println!("{}", e),
+            }
+        }
+        let ticks = self.timestep.advance(frame_time);
+        for _ in 0..ticks {
+            self.tick(self.timestep.step_seconds());
+        }
+    }
+
+    fn tick(&mut self, dt: f32) {
+        self.player.update(&self.camera, &self.world, dt);
+        if self.breaking {
+            self.mine(dt);
+        }
+        // only survival players can be hurt
+        let survival = self.inventory.mode() == GameMode::Survival;
+        for attack in self.mobs.update(&mut self.world, &self.player.feet(), dt) {
+            if survival {
+                self.vitals.hurt_player(&mut self.player, DamageKind::Attack, attack.damage);
+            }
+        }
+        self.world.tick(dt);
+        if survival {
+            self.vitals.update_player(&mut self.player, dt);
+        }
+        self.vitals.update_entities(&mut self.world, dt);
+        self.items.update(self.world.entities_mut(), dt);
+        self.items.pickup(self.world.entities_mut(), &self.player.center(), &mut self.inventory);
+        self.handle_health_events(dt);
+    }
+
+    fn run_command(&mut self, command: Command) {
+        let clock = self.world.clock_mut();
+        match command {
+            Command::QueryTime => (),
+            Command::SetTime(TimeSetting::OfDay(time)) => clock.set_time_of_day(time),
+            Command::SetTime(TimeSetting::Ticks(ticks)) => clock.set_ticks(ticks),
+            Command::FreezeTime(frozen) => clock.freeze(frozen),
+        }
+        let clock = self.world.clock();
+        let state = if clock.is_frozen() { ", frozen" } else { "" };
+        println!("Day {}, time {} ({:.0}%{})", clock.day(), clock.ticks(), clock.time_of_day() * 100., state);
+    }
+
+    // hook for anything that reacts to damage, for now the HUD flash and respawning
+    fn handle_health_events(&mut self, dt: f32) {
+        self.hurt_flash = (self.hurt_flash - dt).max(0.);
+        for event in self.vitals.drain() {
+            match event {
+                HealthEvent::Damaged { target: Target::Player, .. } => self.hurt_flash = HURT_FLASH,
+                HealthEvent::Died { target: Target::Player, .. } => {
+                    let spawn = self.spawn;
+                    self.vitals.respawn(&mut self.player, &spawn);
+                }
+                _ => (),
+            }
+        }
+    }
+
     pub fn render(&mut self, fps: f32) {
+        let sky = self.world.sky();
+        let submerged = self.player.submerged();
+        // underwater the sky is hidden behind the same fog that tints the terrain
+        let (fog_color, fog_density) = match submerged {
+            Some(liquid) => (liquid.fog_color, liquid.fog_density),
+            None => ((sky.horizon.x, sky.horizon.y, sky.horizon.z), 0.0),
+        };
+
         let mut target = self.display.draw();
-        target.clear_color_and_depth((0.529, 0.808, 0.980, 1.0), 1.0);
+        target.clear_color_and_depth((fog_color.0, fog_color.1, fog_color.2, 1.0), 1.0);
 
         self.display
             .gl_window()
@@ -100,34 +281,41 @@ impl Engine {
             .window()
             .set_cursor_visible(!self.grab);
 
-        self.camera.update();
+        self.camera.set_position(self.player.interpolated_eye(self.timestep.alpha()));
 
         // building the uniforms
         let uniforms = uniform! {
           persp_matrix: *(self.camera.get_perspective().as_ref()),
           view_matrix: *(self.camera.get_view().as_ref()),
+          camera_position: *(self.camera.get_position().as_ref()),
+          fog_color: fog_color,
+          fog_density: fog_density,
+          light_direction: *(sky.light_direction.as_ref()),
+          light_color: *(sky.light_color.as_ref()),
+          ambient: sky.ambient,
+          // nearest keeps the pixels of low resolution textures crisp
+          atlas: self.atlas_texture.sampled()
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest),
         };
 
-        // draw coordinates to screen
-        let (w, h) = self.display.get_framebuffer_dimensions();
+        if submerged.is_none() {
+            let view_projection = self.camera.get_perspective() * self.camera.get_view();
+            let sky_uniforms = uniform! {
+              inverse_view_projection: *(glm::inverse(&view_projection).as_ref()),
+              horizon_color: *(sky.horizon.as_ref()),
+              zenith_color: *(sky.zenith.as_ref()),
+              sun_direction: *(sky.sun_direction.as_ref()),
+            };
+            let strip = glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip);
+            target.draw(&self.backdrop.vbo, &strip, &self.sky_program, &sky_uniforms, &Default::default()).unwrap();
+        }
 
-        let mut i = 0;
-        for coord in ["X", "Y", "Z"].iter() {
-            let coord_text = glium_text::TextDisplay::new(&self.text_system,
-                &self.font,
-                &format!("{}: {:.2}", coord, self.camera.get_position()[i]));
-                            
-            const TEXT_SIZE: f32 = 0.04;
-            let matrix: [[f32; 4]; 4] = [
-                [TEXT_SIZE, 0.0, 0.0, 0.0],
-                [0.0, TEXT_SIZE * (w as f32) / (h as f32), 0.0, 0.0],
-                [0.0, 0.0, 1.0, 0.0],
-                [-0.95, 0.9 - ((i as f32) * 0.1), 0.0, 1.0f32]
-            ];
-            i += 1;
-            glium_text::draw(&coord_text, &self.text_system, &mut target, matrix, (1.0, 1.0, 1.0, 1.0));
-        }
-                        
+        // draw coordinates to screen
+        for (i, coord) in ["X", "Y", "Z"].iter().enumerate() {
+            let text = format!("{}: {:.2}", coord, self.camera.get_position()[i]);
+            self.draw_text(&mut target, &text, (-0.95, 0.9 - ((i as f32) * 0.1)), (1.0, 1.0, 1.0, 1.0));
+        }
 
         // let fps_text = glium_text::TextDisplay::new(&self.text_system,
         //     &self.font,
@@ -159,63 +347,397 @@ impl Engine {
 
         let camera_pos = self.camera.get_position();
 
-        self.world.update_chunks(camera_pos, &self.display);
-
+        self.world.update_chunks(camera_pos, &self.display, &self.atlas);
+        self.world.sort_translucent(camera_pos);
+
+        // Sections entirely outside the view aren't sent to the gpu at all, nor are those the
+        // camera can't see into through open space, like caves sealed off underground. Until
+        // the camera's own chunk loads everything in view is drawn.
+        let frustum = Frustum::from_matrix(&(self.camera.get_perspective() * self.camera.get_view()));
+        let visible = self.world.visible_sections(camera_pos, |section| {
+            let corner = section * SECTION_SIZE;
+            let min = vec3(corner.x as f32, corner.y as f32, corner.z as f32).add_scalar(-0.5);
+            frustum.intersects_box(&min, &min.add_scalar(SECTION_SIZE as f32))
+        });
+        let mut stats = CullStats::default();
+        let mut translucent = Vec::new();
         for chunk in self.world.rendered_chunks(camera_pos).iter() {
-            if let Some(instances) = chunk.per_instance() {
-                target
-                    .draw(
-                        (&self.cube, instances),
-                        &INDICES,
-                        &self.program,
-                        &uniforms,
-                        &params,
-                    )
-                    .unwrap();
+            for (section, mesh) in chunk.meshes() {
+                let (min, max) = mesh.bounds;
+                if !frustum.intersects_box(&min, &max) {
+                    stats.culled += 1;
+                    continue;
+                }
+                if visible.as_ref().map_or(false, |v| !v.contains(&vec3(chunk.coordinates[0], section, chunk.coordinates[1]))) {
+                    stats.hidden += 1;
+                    continue;
+                }
+                stats.drawn += 1;
+                if let Some(vertices) = &mesh.opaque {
+                    target
+                        .draw(
+                            vertices,
+                            &INDICES,
+                            &self.chunk_program,
+                            &uniforms,
+                            &params,
+                        )
+                        .unwrap();
+                }
+                if let Some(vertices) = &mesh.translucent {
+                    translucent.push((glm::distance2(&((min + max) / 2.), camera_pos), vertices));
+                }
             }
+        }
+        let text = format!("Sections: {} drawn, {} culled, {} hidden", stats.drawn, stats.culled, stats.hidden);
+        self.draw_text(&mut target, &text, (-0.95, 0.6), (1.0, 1.0, 1.0, 1.0));
+
+        let entities = self.world.entity_instances();
+        if !entities.is_empty() {
+            let instances = VertexBuffer::new(&*self.display, &entities).expect("to create vb");
+            target
+                .draw(
+                    (&self.cube, instances.per_instance().unwrap()),
+                    &INDICES,
+                    &self.program,
+                    &uniforms,
+                    &params,
+                )
+                .unwrap();
+        }
 
+        // Translucent faces go once everything opaque is down, furthest section first and each
+        // section's faces already sorted back to front. They're depth tested but don't write
+        // depth, so one never hides what's behind it.
+        translucent.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        let translucent_params = glium::DrawParameters {
+            depth: glium::Depth {
+                write: false,
+                ..params.depth
+            },
+            ..params.clone()
+        };
+        for (_, vertices) in translucent {
+            target
+                .draw(
+                    vertices,
+                    &INDICES,
+                    &self.chunk_program,
+                    &uniforms,
+                    &translucent_params,
+                )
+                .unwrap();
         }
 
         target.draw(&self.crosshair.vbo, &glium::index::NoIndices(glium::index::PrimitiveType::LinesList), &self.crosshair_program, &glium::uniforms::EmptyUniforms, &Default::default()).unwrap();
-        
+
+        self.draw_hotbar(&mut target);
+        if self.inventory.mode() == GameMode::Survival {
+            self.draw_vitals(&mut target);
+        }
+        if let Some(grid) = &self.crafting {
+            self.draw_crafting(&mut target, grid);
+        }
+
+        if let Some((_, progress)) = self.break_target() {
+            self.draw_text(&mut target, &format!("{:.0}%", progress * 100.), (0.02, -0.07), (1.0, 1.0, 1.0, 1.0));
+        }
+
         target.finish().unwrap();
     }
 
-    pub fn process_click(&mut self, button: event::MouseButton) {
-        println!("Process click");
-        let eye = self.camera.get_position();
+    fn draw_text(&self, target: &mut glium::Frame, text: &str, position: (f32, f32), color: (f32, f32, f32, f32)) {
+        let (w, h) = self.display.get_framebuffer_dimensions();
+        let text = glium_text::TextDisplay::new(&self.text_system, &self.font, text);
+
+        const TEXT_SIZE: f32 = 0.04;
+        let matrix: [[f32; 4]; 4] = [
+            [TEXT_SIZE, 0.0, 0.0, 0.0],
+            [0.0, TEXT_SIZE * (w as f32) / (h as f32), 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [position.0, position.1, 0.0, 1.0f32]
+        ];
+        glium_text::draw(&text, &self.text_system, target, matrix, color);
+    }
+
+    fn draw_hotbar(&self, target: &mut glium::Frame) {
+        const LINES: glium::index::NoIndices =
+            glium::index::NoIndices(glium::index::PrimitiveType::LinesList);
+        const WHITE: (f32, f32, f32, f32) = (1.0, 1.0, 1.0, 1.0);
+        const SELECTED: (f32, f32, f32, f32) = (1.0, 0.9, 0.2, 1.0);
+
+        let selected = self.inventory.selected();
+        target.draw(self.hotbar.outlines(), &LINES, &self.crosshair_program, &glium::uniforms::EmptyUniforms, &Default::default()).unwrap();
+        target.draw(self.hotbar.selection(selected), &LINES, &self.crosshair_program, &glium::uniforms::EmptyUniforms, &Default::default()).unwrap();
+
+        for (slot, stack) in self.inventory.hotbar().iter().enumerate() {
+            let left = Hotbar::slot_left(slot) + 0.015;
+            let color = if slot == selected { SELECTED } else { WHITE };
+            self.draw_text(target, &(slot + 1).to_string(), (left, hotbar::TOP - 0.05), color);
+            if let Some(stack) = stack {
+                self.draw_text(target, &stack.block.name()[..2], (left, hotbar::BOTTOM + 0.05), color);
+                if self.inventory.mode() == GameMode::Survival {
+                    self.draw_text(target, &stack.count.to_string(), (left, hotbar::BOTTOM + 0.01), color);
+                }
+            }
+        }
+
+        if let Some(block) = self.inventory.selected_block() {
+            self.draw_text(target, block.name(), (-0.05, hotbar::TOP + 0.03), WHITE);
+        }
+    }
+
+    fn draw_crafting(&self, target: &mut glium::Frame, grid: &CraftingGrid) {
+        const LINES: glium::index::NoIndices =
+            glium::index::NoIndices(glium::index::PrimitiveType::LinesList);
+        const WHITE: (f32, f32, f32, f32) = (1.0, 1.0, 1.0, 1.0);
+
+        target.draw(self.crafting_panel.outlines(), &LINES, &self.crosshair_program, &glium::uniforms::EmptyUniforms, &Default::default()).unwrap();
+
+        let label = |stack: &ItemStack| format!("{} {}", &stack.block.name()[..2], stack.count);
+        for (cell, stack) in grid.cells().iter().enumerate() {
+            let (left, top) = CraftingPanel::cell_corner(cell);
+            self.draw_text(target, &(cell + 1).to_string(), (left + 0.01, top - 0.04), WHITE);
+            if let Some(stack) = stack {
+                self.draw_text(target, &label(stack), (left + 0.01, top - 0.1), WHITE);
+            }
+        }
+        if let Some(recipe) = grid.result(&self.recipes) {
+            self.draw_text(target, &label(&recipe.output), (crafting_panel::RESULT_LEFT + 0.01, CraftingPanel::result_top() - 0.07), WHITE);
+        }
+
+        // recipe book: the ways of making the selected block
+        let block = match self.inventory.selected_block() {
+            Some(block) => block,
+            None => return,
+        };
+        let recipes = self.recipes.for_output(block);
+        let mut lines = vec![format!("Recipes for {}: {}", block.name(), recipes.len())];
+        for recipe in recipes {
+            lines.extend(recipe.describe());
+        }
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_text(target, line, (crafting_panel::BOOK_LEFT, crafting_panel::BOOK_TOP - i as f32 * 0.06), WHITE);
+        }
+    }
+
+    fn draw_vitals(&self, target: &mut glium::Frame) {
+        const WHITE: (f32, f32, f32, f32) = (1.0, 1.0, 1.0, 1.0);
+        const HURT: (f32, f32, f32, f32) = (1.0, 0.3, 0.3, 1.0);
+
+        let health = self.player.health();
+        let color = if self.hurt_flash > 0. { HURT } else { WHITE };
+        let text = format!("Health {:.0}/{:.0}", health.current.max(0.).ceil(), health.max);
+        self.draw_text(target, &text, (Hotbar::slot_left(0), hotbar::TOP + 0.03), color);
+        if health.air < MAX_AIR {
+            let text = format!("Air {:.0}", health.air.ceil());
+            self.draw_text(target, &text, (Hotbar::slot_left(HOTBAR_SIZE - 2), hotbar::TOP + 0.03), WHITE);
+        }
+    }
+
+    // ray from the eye through the centre of the screen
+    fn view_ray(&self) -> glm::Vec3 {
         let view = self.camera.get_view();
         let proj = self.camera.get_perspective();
 
         let near = glm::unproject(&vec3(1024./2.,768./2.,-1.), &view, &proj, vec4(0.,0.,1024.,768.));
         let far = glm::unproject(&vec3(1024./2.,768./2.,1.), &view, &proj, vec4(0.,0.,1024.,768.));
-        let ray = glm::normalize(&(far-near));
+        glm::normalize(&(far-near))
+    }
+
+    fn target_block(&self) -> Option<(glm::IVec3, glm::IVec3)> {
+        self.world.raycast(self.camera.get_position(), &self.view_ray(), REACH)
+    }
 
-        // let ray = Ray::new([eye[0], eye[1], eye[2]].into(), [ray[0], ray[1], ray[2]].into());
-        // println!("Ray origin: {} dir: {} far {} eye {}", ray.origin, ray.dir, far, eye);
+    // closest mob under the crosshair that isn't behind the targeted block
+    fn target_mob(&self) -> Option<EntityId> {
+        let eye = self.camera.get_position();
+        let direction = self.view_ray();
+        let block_distance = self.target_block()
+            .map_or(std::f32::MAX, |(block, _)| glm::distance(&glm::convert(block), eye));
+        let entities = self.world.entities();
+        entities.mobs.keys()
+            .filter_map(|id| {
+                let offset = entities.transforms[id].position - eye;
+                let along = glm::dot(&offset, &direction);
+                let radius = entities.bodies.get(id).map_or(0.5, |b| b.size / 2.);
+                let miss = glm::length(&(offset - direction * along));
+                if along > 0. && along <= MELEE_REACH && along < block_distance && miss <= radius {
+                    Some((*id, along))
+                } else {
+                    None
+                }
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(id, _)| id)
+    }
+
+    // block under the crosshair being mined and its break progress in [0, 1]
+    pub fn break_target(&self) -> Option<(glm::IVec3, f32)> {
+        self.mining.target()
+    }
+
+    fn mine(&mut self, dt: f32) {
+        let target = self.target_block()
+            .and_then(|(block, _)| self.world.get_block(&block).map(|b| (block, *b)));
+        if let Some(block) = self.mining.update(target, dt) {
+            self.break_block(&block);
+        }
+    }
 
-        let action = match button {
-            event::MouseButton::Left => Some(chunk::IntersectAction::Remove),
-            event::MouseButton::Right => Some(chunk::IntersectAction::Add),
-            _ => None
+    fn break_block(&mut self, block: &glm::IVec3) {
+        if let Some(removed) = self.world.remove_block(block) {
+            if self.inventory.mode() == GameMode::Survival {
+                self.items.spawn(self.world.entities_mut(), removed.item(), 1, glm::convert(*block));
+            }
+        }
+    }
+
+    // uses the targeted switch, otherwise places the selected block against the targeted face
+    fn place_block(&mut self) {
+        let (hit, normal) = match self.target_block() {
+            Some(target) => target,
+            None => return,
         };
+        if circuit::interact(&mut self.world, &hit) {
+            return;
+        }
+        let block_type = match self.inventory.selected_block() {
+            Some(block_type) => block_type,
+            None => return,
+        };
+        let block = hit + normal;
+        let view = self.view_ray();
+        let free = self.world.get_block(&block).map_or(true, |b| b.is_liquid());
+        if free && !self.player.aabb().intersects_voxel(&block) && self.world.set_block(&block, block_type) {
+            circuit::orient(&mut self.world, &block, &view);
+            self.inventory.consume_selected();
+        }
+    }
+
+    // puts stacks in the inventory, dropping whatever doesn't fit at the player's feet
+    fn give(&mut self, stack: ItemStack) {
+        let leftover = self.inventory.add(stack.block, stack.count);
+        if leftover > 0 {
+            self.items.spawn(self.world.entities_mut(), stack.block, leftover, self.player.center());
+        }
+    }
+
+    // closing the grid hands back whatever is still in it
+    fn toggle_crafting(&mut self) {
+        match self.crafting.take() {
+            Some(mut grid) => {
+                for stack in grid.clear() {
+                    let leftover = self.inventory.restore(stack.block, stack.count);
+                    if leftover > 0 {
+                        self.items.spawn(self.world.entities_mut(), stack.block, leftover, self.player.center());
+                    }
+                }
+            }
+            None => self.crafting = Some(CraftingGrid::new()),
+        }
+    }
+
+    fn put_in_grid(&mut self, cell: usize) {
+        if let (Some(grid), Some(block)) = (self.crafting.as_mut(), self.inventory.selected_block()) {
+            if grid.put(cell, block) {
+                self.inventory.consume_selected();
+            }
+        }
+    }
+
+    fn craft(&mut self) {
+        let recipes = &self.recipes;
+        if let Some(output) = self.crafting.as_mut().and_then(|grid| grid.craft(recipes)) {
+            self.give(output);
+        }
+    }
+
+    pub fn save(&mut self) {
+        self.world.save();
+    }
 
-        if let Some(a) = action {
-            self.world.intersect(&eye, &ray, &a);
+    pub fn process_click(&mut self, button: event::MouseButton, pressed: bool) {
+        match button {
+            event::MouseButton::Left if pressed => {
+                if let Some(mob) = self.target_mob() {
+                    self.vitals.hurt_entity(self.world.entities_mut(), mob, DamageKind::Attack, MELEE_DAMAGE);
+                    return;
+                }
+                // creative breaks instantly, survival mines over time while the button is held
+                if self.inventory.mode() == GameMode::Creative {
+                    if let Some((block, _)) = self.target_block() {
+                        self.break_block(&block);
+                    }
+                } else {
+                    self.breaking = true;
+                }
+            }
+            event::MouseButton::Left => {
+                self.breaking = false;
+                self.mining.reset();
+            }
+            event::MouseButton::Right if pressed => self.place_block(),
+            _ => (),
         }
     }
 
-    pub fn process_keyboard(&mut self, pressed: bool, key: VirtualKeyCode, dt: Duration) {
+    pub fn process_scroll(&mut self, lines: f32) {
+        // scrolling down moves right along the hotbar
+        self.inventory.scroll(-lines.signum() as i32);
+    }
+
+    pub fn process_keyboard(&mut self, pressed: bool, key: VirtualKeyCode) {
         if key == VirtualKeyCode::Escape && pressed {
             self.grab = !self.grab;
+        } else if key == VirtualKeyCode::F && pressed {
+            // fly/noclip toggle for building
+            self.player.toggle_flying();
+        } else if key == VirtualKeyCode::C && pressed {
+            self.toggle_crafting();
+        } else if key == VirtualKeyCode::Tab && pressed {
+            // pages the hotbar through the storage rows
+            self.inventory.cycle_rows();
+        } else if self.crafting.is_some() && Self::crafting_key(key) {
+            // while the grid is open the number keys fill its cells from the selected slot
+            if pressed {
+                match key {
+                    VirtualKeyCode::Return => self.craft(),
+                    VirtualKeyCode::Back => self.toggle_crafting(),
+                    _ => self.put_in_grid(Self::hotbar_key(key).unwrap()),
+                }
+            }
+        } else if let Some(slot) = Self::hotbar_key(key) {
+            if pressed {
+                self.inventory.select(slot);
+            }
         } else {
-            self.camera.process_input(pressed, key, dt);
+            self.camera.process_input(pressed, key);
+        }
+    }
+
+    fn crafting_key(key: VirtualKeyCode) -> bool {
+        key == VirtualKeyCode::Return || key == VirtualKeyCode::Back || Self::hotbar_key(key).is_some()
+    }
+
+    fn hotbar_key(key: VirtualKeyCode) -> Option<usize> {
+        match key {
+            VirtualKeyCode::Key1 => Some(0),
+            VirtualKeyCode::Key2 => Some(1),
+            VirtualKeyCode::Key3 => Some(2),
+            VirtualKeyCode::Key4 => Some(3),
+            VirtualKeyCode::Key5 => Some(4),
+            VirtualKeyCode::Key6 => Some(5),
+            VirtualKeyCode::Key7 => Some(6),
+            VirtualKeyCode::Key8 => Some(7),
+            VirtualKeyCode::Key9 => Some(8),
+            _ => None,
         }
     }
 
-    pub fn process_cursor(&mut self, position: (f64, f64), dt: Duration) {
+    pub fn process_cursor(&mut self, position: (f64, f64)) {
         if self.grab {
-            self.camera.process_cursor(position, dt);
+            self.camera.process_cursor(position);
         }
     }
 }
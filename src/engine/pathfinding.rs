@@ -0,0 +1,304 @@
+use crate::engine::world::World;
+use glm::{vec3, IVec3};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+// costs are in half blocks so they stay integers: 2 per block walked, 1 per block climbed or dropped
+const WALK_COST: i32 = 2;
+const VERTICAL_COST: i32 = 1;
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+// Shape of a ground agent in whole blocks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Agent {
+    pub height: i32, // blocks of headroom needed
+    pub step: i32, // highest ledge it can climb
+    pub drop: i32, // deepest fall it will take
+}
+
+impl Agent {
+    // somewhere the agent can stand: solid floor below and clear, dry air for its whole height
+    pub fn can_stand(&self, world: &World, feet: &IVec3) -> bool {
+        world.is_solid(&(feet - vec3(0, 1, 0))) && (0..self.height).all(|y| Self::is_clear(world, &(feet + vec3(0, y, 0))))
+    }
+
+    fn is_clear(world: &World, block: &IVec3) -> bool {
+        !world.is_solid(block) && world.liquid_at(block).is_none()
+    }
+
+    fn column_clear(world: &World, x: i32, z: i32, from: i32, to: i32) -> bool {
+        (from..to).all(|y| Self::is_clear(world, &vec3(x, y, z)))
+    }
+
+    // standing positions reachable in one move, with their cost
+    fn neighbors(&self, world: &World, feet: &IVec3) -> Vec<(IVec3, i32)> {
+        let mut neighbors = Vec::new();
+        for (dx, dz) in DIRECTIONS.iter() {
+            let (x, z) = (feet.x + dx, feet.z + dz);
+            for dy in (-self.drop..=self.step).rev() {
+                let target = vec3(x, feet.y + dy, z);
+                if !self.can_stand(world, &target) {
+                    continue;
+                }
+                // headroom to jump up from here, or a clear shaft to fall down at the target
+                let clear = if dy > 0 {
+                    Self::column_clear(world, feet.x, feet.z, feet.y + self.height, feet.y + self.height + dy)
+                } else {
+                    Self::column_clear(world, x, z, target.y + self.height, feet.y + self.height)
+                };
+                if clear {
+                    neighbors.push((target, WALK_COST + dy.abs() * VERTICAL_COST));
+                }
+                break;
+            }
+        }
+        neighbors
+    }
+
+    fn heuristic(from: &IVec3, to: &IVec3) -> i32 {
+        ((from.x - to.x).abs() + (from.z - to.z).abs()) * WALK_COST + (from.y - to.y).abs() * VERTICAL_COST
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub waypoints: Vec<IVec3>, // smoothed, what an agent should steer between
+    cells: Vec<IVec3>, // every standing position the path goes through
+    agent: Agent,
+    revision: u64, // world revision the path was last checked against
+}
+
+impl Path {
+    pub fn cells(&self) -> &[IVec3] {
+        &self.cells
+    }
+
+    // re-checks only the cells touched by block edits since the last check,
+    // returns false once the path can no longer be walked
+    pub fn is_valid(&mut self, world: &World) -> bool {
+        let cells: Vec<IVec3> = match world.changes_since(self.revision) {
+            // the change log has moved on, check everything
+            None => self.cells.clone(),
+            Some(changes) => self
+                .cells
+                .iter()
+                .filter(|cell| changes.iter().any(|c| self.touches(cell, c)))
+                .copied()
+                .collect(),
+        };
+        self.revision = world.revision();
+        cells.iter().all(|cell| self.agent.can_stand(world, cell))
+    }
+
+    // whether a block change could affect standing at the cell
+    fn touches(&self, cell: &IVec3, change: &IVec3) -> bool {
+        cell.x == change.x && cell.z == change.z && (cell.y - 1..cell.y + self.agent.height).contains(&change.y)
+    }
+}
+
+// A* from one standing position to another, giving up after visiting max_nodes positions
+pub fn find_path(world: &World, agent: Agent, start: IVec3, goal: IVec3, max_nodes: usize) -> Option<Path> {
+    if !agent.can_stand(world, &start) || !agent.can_stand(world, &goal) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<IVec3, IVec3> = HashMap::new();
+    let mut cost: HashMap<IVec3, i32> = HashMap::new();
+    cost.insert(start, 0);
+    // ties are broken on the position so the search is deterministic
+    open.push(Reverse((Agent::heuristic(&start, &goal), (start.x, start.y, start.z))));
+
+    let mut visited = 0;
+    while let Some(Reverse((priority, (x, y, z)))) = open.pop() {
+        let current = vec3(x, y, z);
+        // a cheaper route here was found after this entry was pushed, so it's already expanded
+        if priority > cost[&current] + Agent::heuristic(&current, &goal) {
+            continue;
+        }
+        if current == goal {
+            let cells = reconstruct(&came_from, goal);
+            return Some(Path {
+                waypoints: smooth(world, &agent, &cells),
+                cells,
+                agent,
+                revision: world.revision(),
+            });
+        }
+
+        visited += 1;
+        if visited > max_nodes {
+            return None;
+        }
+
+        let current_cost = cost[&current];
+        for (next, step_cost) in agent.neighbors(world, &current) {
+            let next_cost = current_cost + step_cost;
+            if cost.get(&next).map_or(true, |c| next_cost < *c) {
+                cost.insert(next, next_cost);
+                came_from.insert(next, current);
+                let priority = next_cost + Agent::heuristic(&next, &goal);
+                open.push(Reverse((priority, (next.x, next.y, next.z))));
+            }
+        }
+    }
+    None
+}
+
+fn reconstruct(came_from: &HashMap<IVec3, IVec3>, goal: IVec3) -> Vec<IVec3> {
+    let mut cells = vec![goal];
+    let mut current = goal;
+    while let Some(previous) = came_from.get(&current) {
+        cells.push(*previous);
+        current = *previous;
+    }
+    cells.reverse();
+    cells
+}
+
+// drops waypoints that can be skipped by walking straight across level ground
+fn smooth(world: &World, agent: &Agent, cells: &[IVec3]) -> Vec<IVec3> {
+    let mut waypoints = Vec::new();
+    let mut i = 0;
+    while i < cells.len() {
+        waypoints.push(cells[i]);
+        let mut next = i + 1;
+        while next + 1 < cells.len() && straight_walkable(world, agent, &cells[i], &cells[next + 1]) {
+            next += 1;
+        }
+        i = next;
+    }
+    waypoints
+}
+
+// samples the segment between two positions at the same height
+fn straight_walkable(world: &World, agent: &Agent, from: &IVec3, to: &IVec3) -> bool {
+    if from.y != to.y {
+        return false;
+    }
+    let delta = to - from;
+    let samples = (delta.x.abs().max(delta.z.abs()) * 4).max(1);
+    (0..=samples).all(|s| {
+        let t = s as f32 / samples as f32;
+        let x = (from.x as f32 + delta.x as f32 * t).round() as i32;
+        let z = (from.z as f32 + delta.z as f32 * t).round() as i32;
+        agent.can_stand(world, &vec3(x, from.y, z))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::block::BlockType;
+    use crate::engine::generator::PerlinGenerator;
+    use crate::engine::seed::Seed;
+    use nalgebra::Point2;
+    use std::collections::{HashSet, VecDeque};
+
+    const AGENT: Agent = Agent { height: 2, step: 1, drop: 3 };
+
+    fn assert_connected(world: &World, path: &Path) {
+        for pair in path.cells().windows(2) {
+            let d = pair[1] - pair[0];
+            assert_eq!(d.x.abs() + d.z.abs(), 1, "{} -> {}", pair[0], pair[1]);
+            assert!(AGENT.can_stand(world, &pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_straight_path_is_smoothed() {
+        let world = World::flat(0, false);
+        let path = find_path(&world, AGENT, vec3(1, 17, 1), vec3(12, 17, 1), 1000).unwrap();
+        assert_eq!(path.cells().len(), 12);
+        assert_eq!(path.waypoints, vec![vec3(1, 17, 1), vec3(12, 17, 1)]);
+        assert_connected(&world, &path);
+    }
+
+    #[test]
+    fn test_routes_around_walls_and_over_steps() {
+        let mut world = World::flat(0, false);
+        // a wall two high across x = 6 with a single gap at z = 10
+        for z in 0..16 {
+            if z != 10 {
+                world.set_block(&vec3(6, 17, z), BlockType::STONE);
+                world.set_block(&vec3(6, 18, z), BlockType::STONE);
+            }
+        }
+        let path = find_path(&world, AGENT, vec3(2, 17, 2), vec3(10, 17, 2), 5000).unwrap();
+        assert!(path.cells().contains(&vec3(6, 17, 10)));
+        assert_connected(&world, &path);
+
+        // lowering the wall to one block lets the agent step over it
+        for z in 0..16 {
+            world.remove_block(&vec3(6, 18, z));
+        }
+        let path = find_path(&world, AGENT, vec3(2, 17, 2), vec3(10, 17, 2), 5000).unwrap();
+        assert!(path.cells().contains(&vec3(6, 18, 2)));
+    }
+
+    #[test]
+    fn test_stale_entries_arent_expanded_again() {
+        let mut world = World::flat(0, false);
+        // uneven blocks where cheaper ways to some positions turn up after they're queued
+        for (x, z, height) in [(2, 0, 1), (4, 0, 2), (5, 1, 1)].iter() {
+            for y in 0..*height {
+                world.set_block(&vec3(*x, 17 + y, *z), BlockType::STONE);
+            }
+        }
+        // 21 positions are enough when each is expanded once, re-expanding them takes 23
+        let path = find_path(&world, AGENT, vec3(0, 17, 0), vec3(11, 17, 0), 21).unwrap();
+        assert_connected(&world, &path);
+    }
+
+    #[test]
+    fn test_edits_invalidate_paths() {
+        let mut world = World::flat(0, false);
+        let mut path = find_path(&world, AGENT, vec3(1, 17, 1), vec3(12, 17, 1), 1000).unwrap();
+
+        // unrelated edit
+        world.set_block(&vec3(5, 17, 8), BlockType::STONE);
+        assert!(path.is_valid(&world));
+
+        // pit dug into the path
+        world.remove_block(&vec3(5, 16, 1));
+        assert!(!path.is_valid(&world));
+    }
+
+    #[test]
+    fn test_generated_terrain() {
+        let mut world = World::with_generator(PerlinGenerator::new(&Seed::new(7)));
+        for x in -1..=1 {
+            for z in -1..=1 {
+                world.generate_chunk(Point2::new(x, z));
+            }
+        }
+        let standing: Vec<IVec3> = (0..16)
+            .flat_map(|x| (0..16).map(move |z| (x, z)))
+            .filter_map(|(x, z)| (0..48).map(|y| vec3(x, y, z)).find(|p| AGENT.can_stand(&world, p)))
+            .collect();
+        assert!(standing.len() > 1);
+
+        // the goal is the last surface position the agent can walk to from the first
+        let start = standing[0];
+        let mut reached = HashSet::new();
+        let mut queue = VecDeque::new();
+        reached.insert(start);
+        queue.push_back(start);
+        while let Some(cell) = queue.pop_front() {
+            for (next, _) in AGENT.neighbors(&world, &cell) {
+                let in_chunks = (-16..32).contains(&next.x) && (-16..32).contains(&next.z);
+                if in_chunks && reached.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        let goal = *standing.iter().rev().find(|p| reached.contains(p)).unwrap();
+        assert_ne!(start, goal);
+
+        // each position is expanded at most once, so the reachable ones are always enough
+        let path = find_path(&world, AGENT, start, goal, reached.len()).expect("a path between connected positions");
+        assert_eq!(path.cells()[0], start);
+        assert_eq!(*path.cells().last().unwrap(), goal);
+        assert_connected(&world, &path);
+    }
+}
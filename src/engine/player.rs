@@ -0,0 +1,391 @@
+use crate::camera::CameraState;
+use crate::engine::block::Liquid;
+use crate::engine::health::Health;
+use crate::engine::world::World;
+use glium::glutin::event::VirtualKeyCode;
+use glm::{vec3, IVec3, Vec3};
+
+const WIDTH: f32 = 0.6;
+const HEIGHT: f32 = 1.8;
+pub const EYE_HEIGHT: f32 = 1.62;
+const STEP_HEIGHT: f32 = 1.0;
+const WALK_SPEED: f32 = 4.3;
+const JUMP_SPEED: f32 = 8.5;
+const GRAVITY: f32 = 28.0;
+const TERMINAL_VELOCITY: f32 = 50.0;
+const SWIM_SPEED: f32 = 2.5;
+const SWIM_ACCELERATION: f32 = 18.0;
+const MAX_HEALTH: f32 = 20.0;
+// gap kept between the box and the voxels it touches so it never starts a move overlapping them
+const SKIN: f32 = 0.001;
+
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn intersects_voxel(&self, voxel: &IVec3) -> bool {
+        (0..3).all(|axis| {
+            let center = voxel[axis] as f32;
+            self.min[axis] < center + 0.5 && self.max[axis] > center - 0.5
+        })
+    }
+}
+
+pub struct Player {
+    position: Vec3, // centre of the bottom face of the bounding box
+    previous: Vec3, // position at the start of the last tick, for render interpolation
+    velocity: Vec3,
+    on_ground: bool,
+    flying: bool,
+    in_liquid: Option<Liquid>, // liquid around the middle of the body
+    submerged: Option<Liquid>, // liquid around the eye
+    impact: f32, // downward speed of the last landing, until taken for fall damage
+    health: Health,
+}
+
+impl Player {
+    pub fn new(eye: &Vec3) -> Player {
+        Player {
+            position: eye - vec3(0., EYE_HEIGHT, 0.),
+            previous: eye - vec3(0., EYE_HEIGHT, 0.),
+            velocity: vec3(0., 0., 0.),
+            on_ground: false,
+            flying: false,
+            in_liquid: None,
+            submerged: None,
+            impact: 0.,
+            health: Health::new(MAX_HEALTH),
+        }
+    }
+
+    // back to full health standing still with the eye at the given position
+    pub fn respawn(&mut self, eye: &Vec3) {
+        *self = Player {
+            flying: self.flying,
+            ..Player::new(eye)
+        };
+    }
+
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    pub fn health_mut(&mut self) -> &mut Health {
+        &mut self.health
+    }
+
+    pub fn gravity(&self) -> f32 {
+        GRAVITY
+    }
+
+    // how hard the player last hit the ground, reset to 0 once read
+    pub fn take_impact(&mut self) -> f32 {
+        std::mem::replace(&mut self.impact, 0.)
+    }
+
+    pub fn feet(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn center(&self) -> Vec3 {
+        self.position + vec3(0., HEIGHT / 2., 0.)
+    }
+
+    pub fn eye(&self) -> Vec3 {
+        self.position + vec3(0., EYE_HEIGHT, 0.)
+    }
+
+    // eye position blended between the last two ticks, alpha in [0, 1]
+    pub fn interpolated_eye(&self, alpha: f32) -> Vec3 {
+        glm::lerp(&self.previous, &self.position, alpha) + vec3(0., EYE_HEIGHT, 0.)
+    }
+
+    // the liquid the eye is inside of, for underwater tint and fog
+    pub fn submerged(&self) -> Option<Liquid> {
+        self.submerged
+    }
+
+    pub fn toggle_flying(&mut self) {
+        self.flying = !self.flying;
+        self.velocity = vec3(0., 0., 0.);
+        self.on_ground = false;
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let half = WIDTH / 2.;
+        Aabb {
+            min: self.position - vec3(half, 0., half),
+            max: self.position + vec3(half, HEIGHT, half),
+        }
+    }
+
+    // advances one simulation tick of dt seconds, reading movement keys and heading from the camera
+    pub fn update(&mut self, camera: &CameraState, world: &World, dt: f32) {
+        self.previous = self.position;
+
+        self.in_liquid = world.liquid_at(&World::block_at(&self.center()));
+        self.submerged = world.liquid_at(&World::block_at(&self.eye()));
+
+        if self.flying {
+            self.position += camera.fly_velocity() * dt;
+            return;
+        }
+
+        // hold still until the terrain under us has been generated
+        if !world.is_loaded(&World::block_at(&self.position)) {
+            return;
+        }
+
+        match self.in_liquid {
+            Some(liquid) => self.swim(camera, &liquid, dt),
+            None => self.walk(camera, dt),
+        }
+
+        let falling = -self.velocity.y;
+        let was_on_ground = self.on_ground;
+        let motion = self.velocity * dt;
+        self.move_by(world, &motion);
+        // water breaks a fall
+        if self.on_ground && !was_on_ground && self.in_liquid.is_none() {
+            self.impact = falling;
+        }
+    }
+
+    fn walk(&mut self, camera: &CameraState, dt: f32) {
+        let walk = self.walk_direction(camera) * WALK_SPEED;
+        self.velocity.x = walk.x;
+        self.velocity.z = walk.z;
+
+        if self.on_ground && camera.is_pressed(VirtualKeyCode::Space) {
+            self.velocity.y = JUMP_SPEED;
+        }
+        self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+    }
+
+    // buoyancy cancels most of gravity and drag bleeds off vertical speed,
+    // space and shift swim up and down
+    fn swim(&mut self, camera: &CameraState, liquid: &Liquid, dt: f32) {
+        let walk = self.walk_direction(camera) * SWIM_SPEED;
+        self.velocity.x = walk.x;
+        self.velocity.z = walk.z;
+
+        let mut acceleration = -GRAVITY * (1. - liquid.buoyancy);
+        if camera.is_pressed(VirtualKeyCode::Space) {
+            acceleration += SWIM_ACCELERATION;
+        }
+        if camera.is_pressed(VirtualKeyCode::LShift) || camera.is_pressed(VirtualKeyCode::RShift) {
+            acceleration -= SWIM_ACCELERATION;
+        }
+        self.velocity.y += acceleration * dt;
+        self.velocity.y *= (1. - liquid.drag * dt).max(0.);
+    }
+
+    fn walk_direction(&self, camera: &CameraState) -> Vec3 {
+        let look = camera.get_look();
+        let forward = vec3(look.x, 0., look.z);
+        if forward.norm() == 0. {
+            return forward;
+        }
+        let forward = forward.normalize();
+        let left = glm::cross(&forward, &vec3(0., 1., 0.));
+
+        let mut direction = vec3(0., 0., 0.);
+        if camera.is_pressed(VirtualKeyCode::W) {
+            direction += forward;
+        }
+        if camera.is_pressed(VirtualKeyCode::S) {
+            direction -= forward;
+        }
+        if camera.is_pressed(VirtualKeyCode::A) {
+            direction += left;
+        }
+        if camera.is_pressed(VirtualKeyCode::D) {
+            direction -= left;
+        }
+
+        if direction.norm() > 0. {
+            direction.normalize()
+        } else {
+            direction
+        }
+    }
+
+    fn move_by(&mut self, world: &World, motion: &Vec3) {
+        let moved_y = self.sweep(world, 1, motion.y);
+        self.on_ground = motion.y < 0. && moved_y > motion.y;
+        if moved_y != motion.y {
+            self.velocity.y = 0.;
+        }
+
+        let start = self.position;
+        let moved = self.move_horizontal(world, motion);
+        let can_step = self.on_ground || self.in_liquid.is_some();
+        if !can_step || (moved.x == motion.x && moved.z == motion.z) {
+            return;
+        }
+
+        // blocked while walking on the ground or swimming into a bank, retry the move raised by a block and settle back down
+        let blocked = self.position;
+        self.position = start;
+        let raised = self.sweep(world, 1, STEP_HEIGHT);
+        let stepped = self.move_horizontal(world, motion);
+        self.sweep(world, 1, -raised);
+
+        if stepped.norm() <= moved.norm() + SKIN {
+            self.position = blocked;
+        }
+    }
+
+    fn move_horizontal(&mut self, world: &World, motion: &Vec3) -> Vec3 {
+        let x = self.sweep(world, 0, motion.x);
+        let z = self.sweep(world, 2, motion.z);
+        vec3(x, 0., z)
+    }
+
+    // moves the box along one axis until it touches a solid voxel, returning the distance travelled
+    fn sweep(&mut self, world: &World, axis: usize, delta: f32) -> f32 {
+        if delta == 0. {
+            return 0.;
+        }
+
+        let aabb = self.aabb();
+        let mut min = aabb.min.add_scalar(SKIN);
+        let mut max = aabb.max.add_scalar(-SKIN);
+        min[axis] += delta.min(0.);
+        max[axis] += delta.max(0.);
+        let from = World::block_at(&min);
+        let to = World::block_at(&max);
+
+        let mut allowed = delta;
+        for x in from.x..=to.x {
+            for y in from.y..=to.y {
+                for z in from.z..=to.z {
+                    let voxel = vec3(x, y, z);
+                    if !world.is_solid(&voxel) {
+                        continue;
+                    }
+                    let voxel_min = voxel[axis] as f32 - 0.5;
+                    let voxel_max = voxel[axis] as f32 + 0.5;
+                    if delta > 0. && voxel_min >= aabb.max[axis] - SKIN {
+                        allowed = allowed.min((voxel_min - aabb.max[axis] - SKIN).max(0.));
+                    } else if delta < 0. && voxel_max <= aabb.min[axis] + SKIN {
+                        allowed = allowed.max((voxel_max - aabb.min[axis] + SKIN).min(0.));
+                    }
+                }
+            }
+        }
+
+        self.position[axis] += allowed;
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::block::BlockType;
+
+    // the floor's top face
+    const FLOOR: f32 = 16.5;
+
+    fn standing_at(x: f32, z: f32, height: f32) -> Player {
+        Player::new(&vec3(x, height + EYE_HEIGHT, z))
+    }
+
+    // facing +z, holding the given keys
+    fn holding(keys: &[VirtualKeyCode]) -> CameraState {
+        let mut camera = CameraState::new();
+        for key in keys {
+            camera.process_input(true, *key);
+        }
+        camera
+    }
+
+    fn run(player: &mut Player, camera: &CameraState, world: &World, seconds: f32) {
+        for _ in 0..(seconds * 60.) as usize {
+            player.update(camera, world, 1. / 60.);
+        }
+    }
+
+    // a wall across x at z = 6, as many blocks high as given
+    fn wall(world: &mut World, height: i32) {
+        for x in 5..12 {
+            for y in 17..17 + height {
+                world.set_block(&vec3(x, y, 6), BlockType::STONE);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lands_on_the_floor() {
+        let world = World::flat(0, false);
+        let mut player = standing_at(8., 8., 20.);
+        run(&mut player, &holding(&[]), &world, 2.);
+        assert!(player.on_ground);
+        assert!((player.feet().y - FLOOR).abs() < 0.01, "{}", player.feet().y);
+        assert!(player.take_impact() > 0.);
+
+        let resting = player.feet();
+        run(&mut player, &holding(&[]), &world, 1.);
+        assert_eq!(player.feet(), resting);
+        assert_eq!(player.take_impact(), 0., "standing still isn't another landing");
+    }
+
+    #[test]
+    fn test_jumps_and_comes_back_down() {
+        let world = World::flat(0, false);
+        let mut player = standing_at(8., 8., FLOOR);
+        run(&mut player, &holding(&[]), &world, 0.5);
+        let camera = holding(&[VirtualKeyCode::Space]);
+        player.update(&camera, &world, 1. / 60.);
+        assert!(!player.on_ground);
+        let mut highest: f32 = 0.;
+        for _ in 0..30 {
+            player.update(&holding(&[]), &world, 1. / 60.);
+            highest = highest.max(player.feet().y - FLOOR);
+        }
+        assert!(highest > 1. && highest < 1.5, "{}", highest);
+        run(&mut player, &holding(&[]), &world, 1.);
+        assert!(player.on_ground);
+        assert!((player.feet().y - FLOOR).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_steps_up_a_ledge() {
+        let mut world = World::flat(0, false);
+        // a raised platform starting at z = 6 that runs past where the walk ends
+        for x in 5..12 {
+            for z in 6..16 {
+                world.set_block(&vec3(x, 17, z), BlockType::STONE);
+            }
+        }
+        let mut player = standing_at(8., 3., FLOOR);
+        run(&mut player, &holding(&[VirtualKeyCode::W]), &world, 2.);
+        assert!(player.feet().z > 6.5, "{}", player.feet().z);
+        assert!((player.feet().y - (FLOOR + 1.)).abs() < 0.01, "{}", player.feet().y);
+    }
+
+    #[test]
+    fn test_stops_at_a_wall() {
+        let mut world = World::flat(0, false);
+        wall(&mut world, 2);
+        let mut player = standing_at(8., 3., FLOOR);
+        run(&mut player, &holding(&[VirtualKeyCode::W]), &world, 2.);
+        // the wall's near face is at 5.5
+        assert!((player.feet().z - (5.5 - WIDTH / 2.)).abs() < 0.01, "{}", player.feet().z);
+        assert!((player.feet().y - FLOOR).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_noclip_passes_through() {
+        let mut world = World::flat(0, false);
+        wall(&mut world, 2);
+        let mut player = standing_at(8., 3., 17.5);
+        player.toggle_flying();
+        run(&mut player, &holding(&[VirtualKeyCode::W]), &world, 2.);
+        assert!(player.feet().z > 7., "{}", player.feet().z);
+        assert_eq!(player.feet().y, 17.5, "no gravity while flying");
+    }
+}
@@ -0,0 +1,136 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+// World seed. Any string is accepted: plain integers are taken as the seed's value,
+// everything else is hashed with FNV-1a. std's DefaultHasher is deliberately avoided
+// since its output is not stable between Rust releases. Terrain is generated from a
+// sub-seed, so numeric seeds from before sub-seeds existed now give different worlds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Seed(u64);
+
+// Every source of randomness gets its own sub-seed, keyed by name rather than by
+// order, so adding or reworking one subsystem never reshuffles the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Subsystem {
+    Terrain,
+    Biomes,
+    Caves,
+    Ores,
+    Features,
+    Ticks,
+    Mobs,
+}
+
+impl Subsystem {
+    fn name(&self) -> &'static str {
+        match *self {
+            Subsystem::Terrain => "terrain",
+            Subsystem::Biomes => "biomes",
+            Subsystem::Caves => "caves",
+            Subsystem::Ores => "ores",
+            Subsystem::Features => "features",
+            Subsystem::Ticks => "ticks",
+            Subsystem::Mobs => "mobs",
+        }
+    }
+}
+
+impl Seed {
+    pub fn new(value: u64) -> Seed {
+        Seed(value)
+    }
+
+    pub fn random() -> Seed {
+        Seed(rand::random())
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    pub fn derive(&self, subsystem: Subsystem) -> u64 {
+        splitmix64(self.0 ^ fnv1a(subsystem.name().as_bytes()))
+    }
+
+    // for libraries that only take 32 bit seeds (noise::Seedable)
+    pub fn derive_u32(&self, subsystem: Subsystem) -> u32 {
+        let derived = self.derive(subsystem);
+        (derived ^ (derived >> 32)) as u32
+    }
+}
+
+impl FromStr for Seed {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Seed, Infallible> {
+        Ok(match s.parse::<u64>() {
+            Ok(value) => Seed(value),
+            Err(_) => Seed(fnv1a(s.as_bytes())),
+        })
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_seeds_are_stable() {
+        let a: Seed = "myworld".parse().unwrap();
+        let b: Seed = "myworld".parse().unwrap();
+        assert_eq!(a, b);
+        // pinned so an accidental change to the hash shows up as a test failure
+        assert_eq!(a.value(), 4111032283458698585);
+        assert_ne!(a, "myworld2".parse().unwrap());
+    }
+
+    #[test]
+    fn test_numeric_seeds_are_kept() {
+        let seed: Seed = "12345".parse().unwrap();
+        assert_eq!(seed.value(), 12345);
+    }
+
+    #[test]
+    fn test_subsystems_are_independent() {
+        let seed = Seed::new(42);
+        let subsystems = [
+            Subsystem::Terrain,
+            Subsystem::Biomes,
+            Subsystem::Caves,
+            Subsystem::Ores,
+            Subsystem::Features,
+            Subsystem::Ticks,
+            Subsystem::Mobs,
+        ];
+        for (i, a) in subsystems.iter().enumerate() {
+            for b in subsystems[i + 1..].iter() {
+                assert_ne!(seed.derive(*a), seed.derive(*b));
+            }
+        }
+        assert_eq!(seed.derive(Subsystem::Caves), Seed::new(42).derive(Subsystem::Caves));
+        assert_ne!(seed.derive(Subsystem::Caves), Seed::new(43).derive(Subsystem::Caves));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::engine::generator::WorldGenerator;
+use glm::{vec3, IVec3};
+
+// blocks searched out from the origin in every direction before giving up
+pub const SEARCH_RADIUS: i32 = 512;
+const SEARCH_STEP: i32 = 2; // columns between candidates along each ring
+const FLAT_RADIUS: i32 = 1; // every column this close has to be within a block of the centre
+
+// Looks for somewhere dry and flat to stand in rings of growing size around the origin,
+// reading only the generator's heights so no chunks have to be generated. Returns the
+// block the player's feet go in.
+pub fn find_spawn(generator: &dyn WorldGenerator, radius: i32) -> Option<IVec3> {
+    (0..=radius / SEARCH_STEP)
+        .flat_map(|ring| ring_columns(ring * SEARCH_STEP))
+        .find(|(x, z)| is_safe(generator, *x, *z))
+        .map(|(x, z)| vec3(x, generator.surface_height(x, z) + 1, z))
+}
+
+// columns on the square ring at distance r, walked clockwise from its north-west corner
+fn ring_columns(r: i32) -> Vec<(i32, i32)> {
+    if r == 0 {
+        return vec![(0, 0)];
+    }
+    let mut columns = Vec::new();
+    let mut i = -r;
+    while i < r {
+        columns.push((i, -r));
+        columns.push((r, i));
+        columns.push((-i, r));
+        columns.push((-r, -i));
+        i += SEARCH_STEP;
+    }
+    columns
+}
+
+fn is_safe(generator: &dyn WorldGenerator, x: i32, z: i32) -> bool {
+    let height = generator.surface_height(x, z);
+    (-FLAT_RADIUS..=FLAT_RADIUS).all(|dx| {
+        (-FLAT_RADIUS..=FLAT_RADIUS).all(|dz| {
+            !generator.is_underwater(x + dx, z + dz) && (generator.surface_height(x + dx, z + dz) - height).abs() <= 1
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HEIGHT_OFFSET;
+    use crate::engine::generator::{EdgeMode, HeightmapGenerator, PerlinGenerator, PlanarGenerator, SEA_LEVEL};
+    use crate::engine::seed::Seed;
+    use crate::engine::storage::ChunkStore;
+    use crate::engine::world::World;
+    use image::{GrayImage, Luma};
+
+    // an ocean everywhere except for a flat island and a jagged one closer to the origin
+    fn islands() -> HeightmapGenerator {
+        let mut heights = GrayImage::from_pixel(64, 64, Luma([0]));
+        for x in 10..14 {
+            for z in 0..4 {
+                heights.put_pixel(x, z, Luma([if (x + z) % 2 == 0 { 255 } else { 160 }]));
+            }
+        }
+        for x in 30..36 {
+            for z in 20..26 {
+                heights.put_pixel(x, z, Luma([200]));
+            }
+        }
+        HeightmapGenerator::new(heights, None, EdgeMode::Clamp)
+    }
+
+    #[test]
+    fn test_skips_water_and_rough_ground() {
+        let generator = islands();
+        assert!(generator.is_underwater(0, 0));
+
+        let spawn = find_spawn(&generator, 64).unwrap();
+        assert!((31..35).contains(&spawn.x) && (21..25).contains(&spawn.z), "{}", spawn);
+        assert_eq!(spawn.y, generator.surface_height(spawn.x, spawn.z) + 1);
+    }
+
+    #[test]
+    fn test_gives_up_when_there_is_no_land() {
+        let generator = HeightmapGenerator::new(GrayImage::from_pixel(8, 8, Luma([0])), None, EdgeMode::Tile);
+        assert_eq!(find_spawn(&generator, 32), None);
+    }
+
+    #[test]
+    fn test_generated_terrain_spawns_on_land() {
+        for seed in 0..8 {
+            let generator = PerlinGenerator::new(&Seed::new(seed));
+            let spawn = find_spawn(&generator, SEARCH_RADIUS).unwrap();
+            assert!(spawn.y - 1 >= SEA_LEVEL + HEIGHT_OFFSET);
+            assert!(is_safe(&generator, spawn.x, spawn.z));
+        }
+    }
+
+    #[test]
+    fn test_spawn_is_kept_in_world_metadata() {
+        let mut world = World::with_generator(PlanarGenerator::new());
+        assert_eq!(world.spawn_point(), vec3(0, HEIGHT_OFFSET + 1, 0));
+
+        let mut store = ChunkStore::memory();
+        store.save_metadata("spawn 5 20 -3\n".to_string());
+        world.set_store(store);
+        assert_eq!(world.spawn_point(), vec3(5, 20, -3));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::engine::chunk::ChunkCoordinate;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// Where unloaded chunks (and the entities in them) go. Without a directory they are only
+// kept for the session; with one, every chunk is a small text file next to the others and
+// anything about the world as a whole goes in world.txt.
+pub struct ChunkStore {
+    dir: Option<PathBuf>,
+    memory: HashMap<ChunkCoordinate, String>,
+    metadata: Option<String>,
+}
+
+impl ChunkStore {
+    pub fn memory() -> ChunkStore {
+        ChunkStore {
+            dir: None,
+            memory: HashMap::new(),
+            metadata: None,
+        }
+    }
+
+    pub fn open(dir: PathBuf) -> io::Result<ChunkStore> {
+        fs::create_dir_all(&dir)?;
+        Ok(ChunkStore {
+            dir: Some(dir),
+            memory: HashMap::new(),
+            metadata: None,
+        })
+    }
+
+    fn path(&self, coordinate: &ChunkCoordinate) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("chunk_{}_{}.txt", coordinate[0], coordinate[1])))
+    }
+
+    pub fn save(&mut self, coordinate: ChunkCoordinate, data: String) {
+        match self.path(&coordinate) {
+            Some(path) => {
+                if let Err(e) = fs::write(&path, data) {
+                    println!("Failed to save chunk {}: {}", coordinate, e);
+                }
+            }
+            None => {
+                self.memory.insert(coordinate, data);
+            }
+        }
+    }
+
+    pub fn load(&self, coordinate: &ChunkCoordinate) -> Option<String> {
+        match self.path(coordinate) {
+            Some(path) => fs::read_to_string(path).ok(),
+            None => self.memory.get(coordinate).cloned(),
+        }
+    }
+
+    pub fn save_metadata(&mut self, data: String) {
+        match &self.dir {
+            Some(dir) => {
+                if let Err(e) = fs::write(dir.join("world.txt"), data) {
+                    println!("Failed to save world metadata: {}", e);
+                }
+            }
+            None => self.metadata = Some(data),
+        }
+    }
+
+    pub fn load_metadata(&self) -> Option<String> {
+        match &self.dir {
+            Some(dir) => fs::read_to_string(dir.join("world.txt")).ok(),
+            None => self.metadata.clone(),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::engine::block::BlockType;
+use crate::engine::circuit;
+use crate::engine::falling;
+use crate::engine::flow;
+use crate::engine::world::World;
+use glm::IVec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashMap};
+
+// blocks picked at random in every loaded 16x16x16 section each tick, at 60 ticks a second
+// any one block comes up about once a minute
+pub const RANDOM_TICKS_PER_SECTION: usize = 1;
+
+// scheduled updates run in a single tick, anything past this waits for the next one so a
+// flood of updates (a drained lake) spreads over several frames instead of stalling one
+pub const MAX_SCHEDULED_PER_TICK: usize = 512;
+
+// Runs when a block's scheduled update comes due or when it is picked for a random update.
+// Handlers get the whole world so they can change the block, its neighbours, and schedule
+// further updates.
+pub type TickHandler = fn(&mut World, &IVec3);
+
+#[derive(Clone, Copy, Default)]
+struct Handlers {
+    scheduled: Option<TickHandler>,
+    delay: u64, // ticks between a neighbour changing and the scheduled handler running
+    wakes: bool, // false for handlers that only run when scheduled explicitly
+    random: Option<TickHandler>,
+}
+
+// Keeps the world's block update clock: updates scheduled a number of ticks ahead, the
+// handlers each block type registered, and the random source for random updates.
+pub struct BlockTicks {
+    tick: u64,
+    due: BTreeMap<u64, Vec<IVec3>>,
+    pending: HashMap<IVec3, u64>, // earliest tick each block is scheduled for
+    handlers: HashMap<BlockType, Handlers>,
+    rng: StdRng,
+}
+
+impl BlockTicks {
+    pub fn new(seed: u64) -> BlockTicks {
+        let mut ticks = BlockTicks {
+            tick: 0,
+            due: BTreeMap::new(),
+            pending: HashMap::new(),
+            handlers: HashMap::new(),
+            rng: StdRng::seed_from_u64(seed),
+        };
+        ticks.register_random(BlockType::GRASS, grass_tick);
+        ticks.register_scheduled(BlockType::WATER, flow::FLOW_DELAY, flow::water_tick);
+        for block_type in BlockType::ALL.iter().filter(|b| b.falls()) {
+            ticks.register_scheduled(*block_type, falling::FALL_DELAY, falling::fall_tick);
+        }
+        ticks.register_scheduled(BlockType::REPEATER, circuit::DIODE_DELAY, circuit::diode_tick);
+        ticks.register_scheduled(BlockType::INVERTER, circuit::DIODE_DELAY, circuit::diode_tick);
+        ticks.register_timer(BlockType::BUTTON, circuit::button_tick);
+        ticks
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    // the handler runs whenever the block or one of its neighbours changes, after the delay
+    pub fn register_scheduled(&mut self, block_type: BlockType, delay: u64, handler: TickHandler) {
+        let handlers = self.handlers.entry(block_type).or_default();
+        handlers.scheduled = Some(handler);
+        handlers.delay = delay;
+        handlers.wakes = true;
+    }
+
+    // the handler only runs when something schedules the block itself
+    pub fn register_timer(&mut self, block_type: BlockType, handler: TickHandler) {
+        let handlers = self.handlers.entry(block_type).or_default();
+        handlers.scheduled = Some(handler);
+        handlers.wakes = false;
+    }
+
+    pub fn register_random(&mut self, block_type: BlockType, handler: TickHandler) {
+        self.handlers.entry(block_type).or_default().random = Some(handler);
+    }
+
+    pub fn scheduled_handler(&self, block_type: BlockType) -> Option<TickHandler> {
+        self.handlers.get(&block_type).and_then(|h| h.scheduled)
+    }
+
+    // None for block types that don't react to their neighbours changing
+    pub fn delay(&self, block_type: BlockType) -> Option<u64> {
+        self.handlers.get(&block_type).filter(|h| h.scheduled.is_some() && h.wakes).map(|h| h.delay)
+    }
+
+    pub fn random_handler(&self, block_type: BlockType) -> Option<TickHandler> {
+        self.handlers.get(&block_type).and_then(|h| h.random)
+    }
+
+    // a block is only ever queued once, scheduling it again keeps whichever update is sooner
+    pub fn schedule(&mut self, block: &IVec3, delay: u64) {
+        let at = self.tick + delay.max(1);
+        match self.pending.get(block) {
+            Some(existing) if *existing <= at => return,
+            Some(existing) => {
+                let existing = *existing;
+                if let Some(blocks) = self.due.get_mut(&existing) {
+                    blocks.retain(|b| b != block);
+                }
+            }
+            None => (),
+        }
+        self.pending.insert(*block, at);
+        self.due.entry(at).or_default().push(*block);
+    }
+
+    pub fn is_scheduled(&self, block: &IVec3) -> bool {
+        self.pending.contains_key(block)
+    }
+
+    // moves the clock on by one tick and hands back the blocks whose updates are now due,
+    // oldest first and at most MAX_SCHEDULED_PER_TICK of them
+    pub fn advance(&mut self) -> Vec<IVec3> {
+        self.tick += 1;
+        let mut due = Vec::new();
+        while due.len() < MAX_SCHEDULED_PER_TICK {
+            let at = match self.due.keys().next() {
+                Some(at) if *at <= self.tick => *at,
+                _ => break,
+            };
+            let blocks = self.due.get_mut(&at).unwrap();
+            let count = blocks.len().min(MAX_SCHEDULED_PER_TICK - due.len());
+            due.extend(blocks.drain(..count));
+            if blocks.is_empty() {
+                self.due.remove(&at);
+            }
+        }
+        for block in due.iter() {
+            self.pending.remove(block);
+        }
+        due
+    }
+}
+
+// Grass dies back to dirt once something opaque or a liquid covers it, otherwise it creeps
+// onto a nearby dirt block that has open air above it.
+fn grass_tick(world: &mut World, block: &IVec3) {
+    let above = block + glm::vec3(0, 1, 0);
+    if world.get_block(&above).map_or(false, |b| !b.is_transparent() || b.is_liquid()) {
+        world.set_block(block, BlockType::DIRT);
+        return;
+    }
+
+    let rng = world.block_ticks_mut().rng();
+    let target = block + glm::vec3(rng.gen_range(-1, 2), rng.gen_range(-3, 2), rng.gen_range(-1, 2));
+    let open = world.get_block(&(target + glm::vec3(0, 1, 0))).is_none();
+    if world.get_block(&target) == Some(&BlockType::DIRT) && open {
+        world.set_block(&target, BlockType::GRASS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::vec3;
+
+    fn run(world: &mut World, ticks: usize) {
+        for _ in 0..ticks {
+            world.tick(1. / 60.);
+        }
+    }
+
+    #[test]
+    fn test_scheduled_ticks_fire_once_when_due() {
+        fn crumble(world: &mut World, block: &IVec3) {
+            world.set_block(block, BlockType::SAND);
+        }
+        let mut world = World::flat(0, true);
+        world.block_ticks_mut().register_scheduled(BlockType::STONE, 1, crumble);
+        let stone = vec3(2, 16, 0);
+        world.schedule_tick(&stone, 5);
+        world.schedule_tick(&stone, 8);
+        assert!(world.block_ticks_mut().is_scheduled(&stone));
+
+        run(&mut world, 4);
+        assert_eq!(world.get_block(&stone), Some(&BlockType::STONE));
+        run(&mut world, 1);
+        assert_eq!(world.get_block(&stone), Some(&BlockType::SAND));
+        run(&mut world, 10);
+        assert_eq!(world.get_block(&stone), Some(&BlockType::SAND));
+        // the later schedule went with the first, and the sand's own fall check has run too
+        assert!(!world.block_ticks_mut().is_scheduled(&stone));
+    }
+
+    #[test]
+    fn test_sooner_schedule_wins() {
+        let mut ticks = BlockTicks::new(0);
+        let block = vec3(0, 0, 0);
+        ticks.schedule(&block, 10);
+        ticks.schedule(&block, 2);
+        assert!(ticks.advance().is_empty());
+        assert_eq!(ticks.advance(), vec![block]);
+        for _ in 0..10 {
+            assert!(ticks.advance().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_scheduled_work_is_bounded() {
+        let mut ticks = BlockTicks::new(0);
+        for x in 0..MAX_SCHEDULED_PER_TICK as i32 + 10 {
+            ticks.schedule(&vec3(x, 0, 0), 1);
+        }
+        ticks.schedule(&vec3(0, 1, 0), 2);
+        assert_eq!(ticks.advance().len(), MAX_SCHEDULED_PER_TICK);
+        let rest = ticks.advance();
+        assert_eq!(rest.len(), 11, "the leftovers run before anything due later");
+        assert_eq!(rest.last(), Some(&vec3(0, 1, 0)));
+    }
+
+    #[test]
+    fn test_random_ticks_reach_loaded_sections() {
+        fn crumble(world: &mut World, block: &IVec3) {
+            world.set_block(block, BlockType::SAND);
+        }
+        let mut world = World::flat(0, true);
+        world.block_ticks_mut().register_random(BlockType::STONE, crumble);
+        for y in 40..44 {
+            world.set_block(&vec3(2, y, 0), BlockType::STONE);
+        }
+        // a quarter of the floor is stone, plus a few blocks up in the next section
+        let stones = |world: &World| (0..16).flat_map(|x| (0..16).map(move |z| vec3(x, 16, z)))
+            .chain((40..44).map(|y| vec3(2, y, 0)))
+            .filter(|b| world.get_block(b) == Some(&BlockType::STONE))
+            .count();
+        assert_eq!(stones(&world), 68);
+
+        run(&mut world, 4096 * 4);
+        assert!(stones(&world) < 40, "{}", stones(&world));
+    }
+
+    #[test]
+    fn test_grass_spreads_and_dies() {
+        let mut world = World::flat(0, true);
+        let grass = vec3(3, 16, 0);
+        let dirt = vec3(4, 16, 0);
+        assert_eq!(world.get_block(&dirt), Some(&BlockType::DIRT));
+        for _ in 0..200 {
+            grass_tick(&mut world, &grass);
+        }
+        assert_eq!(world.get_block(&dirt), Some(&BlockType::GRASS));
+
+        world.set_block(&vec3(3, 17, 0), BlockType::STONE);
+        grass_tick(&mut world, &grass);
+        assert_eq!(world.get_block(&grass), Some(&BlockType::DIRT));
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+// frames longer than this are dropped rather than simulated, so a stall (window drag,
+// breakpoint) doesn't leave the loop trying to catch up forever
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+// Accumulates real frame time and hands it out as a whole number of fixed-length ticks,
+// keeping the leftover fraction for interpolating the render between the last two ticks.
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: u32) -> FixedTimestep {
+        FixedTimestep {
+            step: Duration::from_secs(1) / ticks_per_second,
+            accumulator: Duration::from_secs(0),
+        }
+    }
+
+    pub fn step_seconds(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    // returns the number of ticks to simulate for this frame
+    pub fn advance(&mut self, frame_time: Duration) -> u32 {
+        self.accumulator += frame_time.min(MAX_FRAME_TIME);
+        let mut ticks = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    // how far the render is between the previous tick (0) and the current one (1)
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticks_are_independent_of_frame_rate() {
+        let mut slow = FixedTimestep::new(60);
+        let mut fast = FixedTimestep::new(60);
+        let slow_ticks: u32 = (0..30).map(|_| slow.advance(Duration::from_millis(33))).sum();
+        let fast_ticks: u32 = (0..132).map(|_| fast.advance(Duration::from_nanos(7_500_000))).sum();
+        assert_eq!(slow_ticks, 59);
+        assert_eq!(fast_ticks, 59);
+    }
+
+    #[test]
+    fn test_alpha_is_leftover_fraction() {
+        let mut timestep = FixedTimestep::new(10);
+        assert_eq!(timestep.advance(Duration::from_millis(150)), 1);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_long_frames_are_clamped() {
+        let mut timestep = FixedTimestep::new(60);
+        assert_eq!(timestep.advance(Duration::from_secs(5)), 15);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::engine::chunk::CHUNK_SIZE;
+use glm::{vec3, IVec3};
+use std::collections::{HashSet, VecDeque};
+
+// sections are chunk columns cut into cubes, counted up from world y 0
+pub const SECTION_SIZE: i32 = CHUNK_SIZE;
+// anything above this is taken to be open sky, 256 blocks up
+pub const TOP_SECTION: i32 = 15;
+
+// the way out through each face of a section, the opposite of face i is i ^ 1
+const FACES: [[i32; 3]; 6] = [[1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+
+pub fn section_of(y: i32) -> i32 {
+    y.div_euclid(SECTION_SIZE)
+}
+
+fn opposite(face: usize) -> usize {
+    face ^ 1
+}
+
+// Which pairs of a section's faces are joined by space that isn't opaque, one bit for each of
+// the 6 x 6 pairs. Something seen through one face can only be behind a face it connects to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Connectivity(u64);
+
+impl Connectivity {
+    pub const OPEN: Connectivity = Connectivity((1 << 36) - 1);
+    pub const CLOSED: Connectivity = Connectivity(0);
+
+    pub fn connects(&self, a: usize, b: usize) -> bool {
+        self.0 & (1 << (a * 6 + b)) != 0
+    }
+
+    fn connect(&mut self, a: usize, b: usize) {
+        self.0 |= 1 << (a * 6 + b) | 1 << (b * 6 + a);
+    }
+
+    // Flood fills each pocket of open cells in turn, joining every face the pocket touches.
+    // Cells are given relative to the section's lowest corner.
+    pub fn compute(opaque: impl Fn(&IVec3) -> bool) -> Connectivity {
+        let size = SECTION_SIZE;
+        let index = |cell: &IVec3| (cell.x + size * (cell.y + size * cell.z)) as usize;
+        let inside = |cell: &IVec3| (0..3).all(|a| cell[a] >= 0 && cell[a] < size);
+        let mut filled = vec![false; (size * size * size) as usize];
+        let mut connectivity = Connectivity::CLOSED;
+
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let seed = vec3(x, y, z);
+                    if filled[index(&seed)] || opaque(&seed) {
+                        continue;
+                    }
+                    filled[index(&seed)] = true;
+                    let mut touched = [false; 6];
+                    let mut queue = VecDeque::new();
+                    queue.push_back(seed);
+                    while let Some(cell) = queue.pop_front() {
+                        for (face, offset) in FACES.iter().enumerate() {
+                            let next = cell + vec3(offset[0], offset[1], offset[2]);
+                            if !inside(&next) {
+                                touched[face] = true;
+                            } else if !filled[index(&next)] && !opaque(&next) {
+                                filled[index(&next)] = true;
+                                queue.push_back(next);
+                            }
+                        }
+                    }
+                    for a in (0..6).filter(|a| touched[*a]) {
+                        for b in (0..6).filter(|b| touched[*b]) {
+                            connectivity.connect(a, b);
+                        }
+                    }
+                }
+            }
+        }
+        connectivity
+    }
+}
+
+// Walks out from the camera's section (chunk x, section, chunk z) a section at a time. A
+// section is only left through a face its open space joins to the one it was entered by, the
+// walk never turns back towards the camera, and sections out of view aren't entered. Sections
+// with no connectivity are unloaded or outside the world and stop the walk.
+pub fn visible_sections(
+    start: IVec3,
+    connectivity: impl Fn(&IVec3) -> Option<Connectivity>,
+    in_view: impl Fn(&IVec3) -> bool,
+) -> HashSet<IVec3> {
+    let mut visible = HashSet::new();
+    let open = match connectivity(&start) {
+        Some(open) => open,
+        None => return visible,
+    };
+    visible.insert(start);
+    // each section with its connectivity, the face it was entered by and the directions taken
+    let mut queue = VecDeque::new();
+    queue.push_back((start, open, None, 0u8));
+    while let Some((section, open, entered, travelled)) = queue.pop_front() {
+        for (face, offset) in FACES.iter().enumerate() {
+            if travelled & 1 << opposite(face) != 0 {
+                continue;
+            }
+            if let Some(entered) = entered {
+                if !open.connects(entered, face) {
+                    continue;
+                }
+            }
+            let next = section + vec3(offset[0], offset[1], offset[2]);
+            if visible.contains(&next) || !in_view(&next) {
+                continue;
+            }
+            if let Some(next_open) = connectivity(&next) {
+                visible.insert(next);
+                queue.push_back((next, next_open, Some(opposite(face)), travelled | 1 << face));
+            }
+        }
+    }
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HEIGHT_OFFSET;
+    use crate::engine::block::BlockType;
+    use crate::engine::chunk::Chunk;
+    use crate::engine::world::World;
+    use nalgebra::Point2;
+    use std::collections::HashMap;
+
+    const EAST: usize = 0;
+    const WEST: usize = 1;
+    const UP: usize = 2;
+    const DOWN: usize = 3;
+
+    #[test]
+    fn test_empty_and_solid_sections() {
+        assert_eq!(Connectivity::compute(|_| false), Connectivity::OPEN);
+        assert_eq!(Connectivity::compute(|_| true), Connectivity::CLOSED);
+    }
+
+    #[test]
+    fn test_floor_splits_top_from_bottom() {
+        let connectivity = Connectivity::compute(|cell| cell.y == 8);
+        assert!(!connectivity.connects(UP, DOWN));
+        assert!(connectivity.connects(EAST, UP));
+        assert!(connectivity.connects(EAST, DOWN));
+        assert!(connectivity.connects(EAST, WEST));
+    }
+
+    #[test]
+    fn test_tunnel_joins_only_its_ends() {
+        let connectivity = Connectivity::compute(|cell| !(cell.y == 5 && cell.z == 5));
+        assert!(connectivity.connects(EAST, WEST));
+        assert!(!connectivity.connects(EAST, UP));
+        assert!(!connectivity.connects(UP, DOWN));
+    }
+
+    // a column of sections with air above a solid layer at section 2
+    fn column(layer: Connectivity) -> HashMap<IVec3, Connectivity> {
+        (0..=4)
+            .map(|y| (vec3(0, y, 0), if y == 2 { layer } else { Connectivity::OPEN }))
+            .collect()
+    }
+
+    #[test]
+    fn test_sealed_caves_are_hidden() {
+        let sections = column(Connectivity::CLOSED);
+        let visible = visible_sections(vec3(0, 4, 0), |s| sections.get(s).copied(), |_| true);
+        assert!(visible.contains(&vec3(0, 2, 0)), "the top of the layer can be seen");
+        assert!(!visible.contains(&vec3(0, 1, 0)));
+        assert!(!visible.contains(&vec3(0, 0, 0)));
+    }
+
+    #[test]
+    fn test_shafts_let_the_walk_through() {
+        let sections = column(Connectivity::compute(|cell| !(cell.x == 3 && cell.z == 3)));
+        let visible = visible_sections(vec3(0, 4, 0), |s| sections.get(s).copied(), |_| true);
+        assert_eq!(visible.len(), 5);
+    }
+
+    #[test]
+    fn test_stops_at_the_view_and_the_loaded_world() {
+        let sections = column(Connectivity::OPEN);
+        let visible = visible_sections(vec3(0, 4, 0), |s| sections.get(s).copied(), |s| s.y >= 3);
+        assert_eq!(visible.len(), 2);
+        assert!(visible_sections(vec3(5, 0, 0), |s| sections.get(s).copied(), |_| true).is_empty());
+    }
+
+    #[test]
+    fn test_chunk_sections() {
+        let mut chunk = Chunk::new(Point2::new(0, 0));
+        // a stone layer through the middle of section 1
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.add_block([x, 24 - HEIGHT_OFFSET, z].into(), BlockType::STONE);
+            }
+        }
+        chunk.update_connectivity();
+        assert!(!chunk.connectivity(1).connects(UP, DOWN));
+        assert_eq!(chunk.connectivity(0), Connectivity::OPEN);
+        assert_eq!(chunk.connectivity(2), Connectivity::OPEN);
+    }
+
+    #[test]
+    fn test_cameras_below_the_world_start_at_the_bottom() {
+        let world = World::flat(1, false);
+        let visible = world.visible_sections(&glm::vec3(8., -20., 8.), |_| true).unwrap();
+        assert!(visible.contains(&vec3(0, 0, 0)));
+        assert!(visible.contains(&vec3(1, 1, 0)));
+    }
+
+    #[test]
+    fn test_nothing_is_hidden_before_the_cameras_chunk_loads() {
+        let world = World::flat(0, false);
+        assert!(world.visible_sections(&glm::vec3(8., 20., 8.), |_| true).is_some());
+        assert!(world.visible_sections(&glm::vec3(40., 20., 8.), |_| true).is_none());
+    }
+}
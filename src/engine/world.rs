@@ -1,44 +1,491 @@
-use crate::config::RENDER_DISTANCE;
-use crate::engine::chunk::{Chunk, ChunkCoordinate, CHUNK_SIZE, IntersectAction};
-use crate::engine::generator::{PerlinGenerator, WorldGenerator};
+use crate::config::{HEIGHT_OFFSET, RENDER_DISTANCE};
+use crate::engine::atlas::Atlas;
+use crate::engine::block::{BlockType, Liquid};
+use crate::engine::circuit::{self, Circuits};
+use crate::engine::clock::{Clock, Sky};
+use crate::engine::chunk::{BlockCoordinate, Chunk, ChunkCoordinate, CHUNK_SIZE};
+use crate::engine::entity::Entities;
+use crate::engine::flow::SOURCE_LEVEL;
+use crate::engine::generator::{Biome, Climate, PerlinGenerator, WorldGenerator};
+#[cfg(test)]
+use crate::engine::generator::PlanarGenerator;
+use crate::engine::light::{self, Channel};
+use crate::engine::mesh::{self, SectionVertices};
+use crate::engine::seed::{Seed, Subsystem};
+use crate::engine::spawn;
+use crate::engine::storage::ChunkStore;
+use crate::primitives::InstanceAttr;
+use crate::engine::ticks::{self, BlockTicks};
+use crate::engine::visibility;
 use glium::Display;
-use glm::Vec3;
+use glm::{IVec3, Vec3};
 use nalgebra::Point2;
-use std::collections::HashMap;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use super::march::VoxelMarch;
 
 pub struct World {
     generator: Arc<Mutex<dyn WorldGenerator + Send>>,
+    climate: Option<Climate>, // a copy of the generator's, so biomes don't wait on its lock
     chunks: HashMap<ChunkCoordinate, Chunk>,
     chunk_queue: Arc<Mutex<Vec<Chunk>>>,
     generated: Vec<ChunkCoordinate>,
+    entities: Entities,
+    store: ChunkStore,
+    revision: u64, // bumped on every block edit after generation
+    changes: VecDeque<(u64, IVec3)>,
+    spawn: Option<IVec3>, // found on first use, then kept in the world metadata
+    block_ticks: BlockTicks,
+    clock: Clock,
+    circuits: Circuits,
 }
 
+// how many recent block edits are remembered for changes_since
+const CHANGE_LOG_SIZE: usize = 4096;
+
+// chunks this far (in chunks) beyond the render distance are saved and dropped
+pub const UNLOAD_MARGIN: i32 = 2;
+
+// columns are searched downwards from here for their surface, and are lit up to it
+pub const SKY_HEIGHT: i32 = HEIGHT_OFFSET + 48;
+
 impl World {
-    pub fn new(seed: u32) -> World {
+    pub fn new(seed: &Seed) -> World {
+        World::seeded(PerlinGenerator::new(seed), seed)
+    }
+
+    // a world whose terrain comes from elsewhere, like a heightmap, and whose random ticks
+    // still follow the seed
+    pub fn seeded<G: WorldGenerator + Send + 'static>(generator: G, seed: &Seed) -> World {
+        let mut world = World::with_generator(generator);
+        world.block_ticks = BlockTicks::new(seed.derive(Subsystem::Ticks));
+        world
+    }
+
+    pub fn with_generator<G: WorldGenerator + Send + 'static>(generator: G) -> World {
         World {
-            generator: Arc::new(Mutex::new(PerlinGenerator::new(seed))),
+            climate: generator.climate(),
+            generator: Arc::new(Mutex::new(generator)),
             chunks: HashMap::new(),
             chunk_queue: Arc::new(Mutex::new(Vec::new())),
             generated: Vec::new(),
+            entities: Entities::new(),
+            store: ChunkStore::memory(),
+            revision: 0,
+            changes: VecDeque::new(),
+            spawn: None,
+            block_ticks: BlockTicks::new(0),
+            clock: Clock::new(),
+            circuits: Circuits::new(),
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // blocks edited after the given revision, None once the log no longer reaches back that
+    // far, in which case the caller has to assume anything changed
+    pub fn changes_since(&self, revision: u64) -> Option<Vec<IVec3>> {
+        if revision >= self.revision {
+            return Some(Vec::new());
+        }
+        match self.changes.front() {
+            Some((oldest, _)) if *oldest <= revision + 1 => Some(
+                self.changes.iter().filter(|(r, _)| *r > revision).map(|(_, block)| *block).collect()
+            ),
+            _ => None,
+        }
+    }
+
+    fn record_change(&mut self, block: &IVec3) {
+        self.revision += 1;
+        self.changes.push_back((self.revision, *block));
+        if self.changes.len() > CHANGE_LOG_SIZE {
+            self.changes.pop_front();
+        }
+    }
+
+    pub fn set_store(&mut self, store: ChunkStore) {
+        self.store = store;
+        self.spawn = None;
+        if let Some(clock) = self.store.load_metadata().and_then(|data| Clock::load(&data)) {
+            self.clock = clock;
+        }
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut Clock {
+        &mut self.clock
+    }
+
+    pub fn sky(&self) -> Sky {
+        self.clock.sky()
+    }
+
+    pub fn circuits(&self) -> &Circuits {
+        &self.circuits
+    }
+
+    pub fn circuits_mut(&mut self) -> &mut Circuits {
+        &mut self.circuits
+    }
+
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    pub fn entities_mut(&mut self) -> &mut Entities {
+        &mut self.entities
+    }
+
+    pub fn block_ticks_mut(&mut self) -> &mut BlockTicks {
+        &mut self.block_ticks
+    }
+
+    // asks for the block's scheduled handler to run the given number of ticks from now
+    pub fn schedule_tick(&mut self, block: &IVec3, delay: u64) {
+        self.block_ticks.schedule(block, delay);
+    }
+
+    // advances everything in the world that moves on its own by one simulation tick
+    pub fn tick(&mut self, dt: f32) {
+        self.clock.tick();
+        self.update_blocks();
+        circuit::update(self);
+
+        // entity physics reads blocks while moving entities, so take them out while it runs
+        let mut entities = std::mem::take(&mut self.entities);
+        entities.update(self, dt);
+        self.entities = entities;
+    }
+
+    // runs the scheduled updates that are due, then random updates in every loaded section;
+    // updates due in chunks that have since unloaded are dropped
+    fn update_blocks(&mut self) {
+        for block in self.block_ticks.advance() {
+            let handler = self.get_block(&block).and_then(|b| self.block_ticks.scheduled_handler(*b));
+            if let Some(handler) = handler {
+                handler(self, &block);
+            }
+        }
+
+        // sorted so a seeded world ticks the same blocks every time
+        let mut loaded: Vec<ChunkCoordinate> = self.chunks.keys().copied().collect();
+        loaded.sort_by_key(|c| (c[0], c[1]));
+        for chunk in loaded {
+            for section in (0..SKY_HEIGHT).step_by(CHUNK_SIZE as usize) {
+                for _ in 0..ticks::RANDOM_TICKS_PER_SECTION {
+                    let rng = self.block_ticks.rng();
+                    let block = glm::vec3(
+                        chunk[0] * CHUNK_SIZE + rng.gen_range(0, CHUNK_SIZE),
+                        section + rng.gen_range(0, CHUNK_SIZE),
+                        chunk[1] * CHUNK_SIZE + rng.gen_range(0, CHUNK_SIZE),
+                    );
+                    let handler = self.get_block(&block).and_then(|b| self.block_ticks.random_handler(*b));
+                    if let Some(handler) = handler {
+                        handler(self, &block);
+                    }
+                }
+            }
         }
     }
 
     fn convert_to_chunk(position: &Vec3) -> ChunkCoordinate {
-        Point2::new(
-            (position[0] / CHUNK_SIZE as f32) as i32,
-            (position[2] / CHUNK_SIZE as f32) as i32,
-        )
-        // glm::vec2((position[0] / CHUNK_SIZE as f32).floor(), (position[2] / CHUNK_SIZE as f32).floor())
+        Self::locate(&Self::block_at(position)).0
+    }
+
+    // splits a block position in world space into its chunk and its position inside that chunk
+    pub fn locate(block: &IVec3) -> (ChunkCoordinate, BlockCoordinate) {
+        let chunk = Point2::new(block.x.div_euclid(CHUNK_SIZE), block.z.div_euclid(CHUNK_SIZE));
+        let local = [block.x.rem_euclid(CHUNK_SIZE), block.y - HEIGHT_OFFSET, block.z.rem_euclid(CHUNK_SIZE)].into();
+        (chunk, local)
     }
 
-    pub fn update_chunks(&mut self, position: &Vec3, display: &Display) {
+    // block containing a point in world space, blocks are centred on integer coordinates
+    pub fn block_at(position: &Vec3) -> IVec3 {
+        glm::try_convert(glm::floor(&position.add_scalar(0.5))).unwrap()
+    }
+
+    pub fn is_loaded(&self, block: &IVec3) -> bool {
+        self.chunks.contains_key(&Self::locate(block).0)
+    }
+
+    pub fn get_block(&self, block: &IVec3) -> Option<&BlockType> {
+        let (chunk, local) = Self::locate(block);
+        self.chunks.get(&chunk).and_then(|c| c.get(&local)).map(|b| &b.block_type)
+    }
+
+    pub fn liquid_at(&self, block: &IVec3) -> Option<Liquid> {
+        self.get_block(block).and_then(|b| b.liquid())
+    }
+
+    // SOURCE_LEVEL for still water, less the further it has flowed, 0 where there's no liquid
+    pub fn liquid_level(&self, block: &IVec3) -> u8 {
+        let (chunk, local) = Self::locate(block);
+        match self.chunks.get(&chunk) {
+            Some(chunk) if chunk.get(&local).map_or(false, |b| b.block_type.is_liquid()) => {
+                chunk.level(&local).unwrap_or(SOURCE_LEVEL)
+            }
+            _ => 0,
+        }
+    }
+
+    // places liquid with the given level, false if it was already there or isn't loaded so
+    // settled liquid doesn't keep rebuilding its chunk
+    pub fn set_liquid(&mut self, block: &IVec3, block_type: BlockType, level: u8) -> bool {
+        if self.get_block(block) == Some(&block_type) && self.liquid_level(block) == level {
+            return false;
+        }
+        let (chunk, local) = Self::locate(block);
+        match self.chunks.get_mut(&chunk) {
+            Some(chunk) => {
+                chunk.set_block(local, block_type);
+                chunk.set_level(local, if level < SOURCE_LEVEL { Some(level) } else { None });
+                self.changed(block);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // extra data some blocks keep (a lever's position, a repeater's facing), 0 by default
+    pub fn block_state(&self, block: &IVec3) -> u8 {
+        let (chunk, local) = Self::locate(block);
+        self.chunks.get(&chunk).map_or(0, |c| c.state(&local))
+    }
+
+    // false if there's no block there or the state is unchanged
+    pub fn set_block_state(&mut self, block: &IVec3, state: u8) -> bool {
+        if self.get_block(block).is_none() || self.block_state(block) == state {
+            return false;
+        }
+        let (chunk, local) = Self::locate(block);
+        self.chunks.get_mut(&chunk).unwrap().set_state(local, state);
+        self.changed(block);
+        true
+    }
+
+    // blocks in chunks that haven't loaded yet count as solid so nothing falls through them
+    pub fn is_solid(&self, block: &IVec3) -> bool {
+        if !self.is_loaded(block) {
+            return true;
+        }
+        self.get_block(block).map_or(false, |b| b.is_solid())
+    }
+
+    pub fn biome(&self, block: &IVec3) -> Biome {
+        self.climate.map_or(Biome::Plains, |climate| climate.biome(block.x, block.z))
+    }
+
+    // topmost solid block of a loaded column
+    pub fn surface(&self, x: i32, z: i32) -> Option<IVec3> {
+        if !self.is_loaded(&glm::vec3(x, 0, z)) {
+            return None;
+        }
+        (-HEIGHT_OFFSET..=SKY_HEIGHT)
+            .rev()
+            .map(|y| glm::vec3(x, y, z))
+            .find(|block| self.get_block(block).map_or(false, |b| b.is_solid()))
+    }
+
+    pub fn sky_light(&self, block: &IVec3) -> u8 {
+        self.light_value(block, Channel::Sky)
+    }
+
+    pub fn block_light(&self, block: &IVec3) -> u8 {
+        self.light_value(block, Channel::Block)
+    }
+
+    // whichever of daylight and block light is brighter
+    pub fn light(&self, block: &IVec3) -> u8 {
+        self.sky_light(block).max(self.block_light(block))
+    }
+
+    // unloaded chunks are dark
+    pub fn light_value(&self, block: &IVec3, channel: Channel) -> u8 {
+        let (chunk, local) = Self::locate(block);
+        self.chunks.get(&chunk).map_or(0, |c| c.light(&local, channel))
+    }
+
+    // false where light can't be stored. Faces are lit by the cell in front of them, so a
+    // change on a chunk's edge also dirties the neighbour whose faces look into it.
+    pub fn set_light(&mut self, block: &IVec3, channel: Channel, value: u8) -> bool {
+        let (chunk, local) = Self::locate(block);
+        let changed = self.chunks.get(&chunk).map_or(false, |c| c.light(&local, channel) != value);
+        let stored = self.chunks.get_mut(&chunk).map_or(false, |c| c.set_light(&local, channel, value));
+        if changed && stored {
+            self.invalidate_near(block);
+        }
+        stored
+    }
+
+    // faces in the chunks around a block on a chunk's edge are lit and occluded by it too
+    fn invalidate_near(&mut self, block: &IVec3) {
+        let (own, _) = Self::locate(block);
+        for x in -1..=1 {
+            for z in -1..=1 {
+                let (chunk, _) = Self::locate(&(block + glm::vec3(x, 0, z)));
+                if chunk != own {
+                    if let Some(chunk) = self.chunks.get_mut(&chunk) {
+                        chunk.invalidate();
+                    }
+                }
+            }
+        }
+    }
+
+    // the visible faces of a chunk's blocks
+    // the chunk's faces split up by section, so sections can be culled on their own
+    fn chunk_mesh(&self, coordinate: &ChunkCoordinate, atlas: &Atlas) -> BTreeMap<i32, SectionVertices> {
+        let mut sections: BTreeMap<i32, SectionVertices> = BTreeMap::new();
+        for block in self.chunks[coordinate].rendered() {
+            let position = Self::block_at(&glm::vec3(block.position.0, block.position.1, block.position.2));
+            let section = sections.entry(visibility::section_of(position.y)).or_default();
+            let vertices = if block.block_type.is_translucent() { &mut section.translucent } else { &mut section.opaque };
+            mesh::block_faces(self, atlas, &position, block.block_type, vertices);
+        }
+        sections
+    }
+
+    // entities are shaded by the light where they stand, sky light dimmed by the time of day
+    // how lit a block is right now, the sky channel dimmed for the time of day as the chunk
+    // shader does
+    pub fn brightness(&self, block: &IVec3) -> f32 {
+        let sky = light::brightness(self.sky_light(block)) * self.sky().ambient;
+        sky.max(light::brightness(self.block_light(block)))
+    }
+
+    pub fn entity_instances(&self) -> Vec<InstanceAttr> {
+        let mut instances = self.entities.instances();
+        for instance in instances.iter_mut() {
+            let (x, y, z) = instance.world_position;
+            let brightness = self.brightness(&Self::block_at(&glm::vec3(x, y, z)));
+            instance.light_positive = (brightness, brightness, brightness);
+            instance.light_negative = instance.light_positive;
+        }
+        instances
+    }
+
+    // generates a chunk on the calling thread, for when it's needed before the loader gets to it
+    pub fn generate_chunk(&mut self, coordinate: ChunkCoordinate) {
+        if self.load_chunk(coordinate) {
+            return;
+        }
+        let mut chunk = self.generator.lock().unwrap().generate(coordinate);
+        chunk.update_visible();
+        light::light_chunk(&mut chunk);
+        self.generated.push(coordinate);
+        self.insert_chunk(chunk);
+    }
+
+    fn insert_chunk(&mut self, chunk: Chunk) {
+        let coordinate = chunk.coordinates;
+        self.chunks.insert(coordinate, chunk);
+        // the neighbours' edge faces were meshed against nothing
+        for x in -1..=1 {
+            for z in -1..=1 {
+                if let Some(neighbour) = self.chunks.get_mut(&Point2::new(coordinate[0] + x, coordinate[1] + z)) {
+                    neighbour.invalidate();
+                }
+            }
+        }
+        light::stitch(self, coordinate);
+        // wire strengths aren't saved, the chunk's circuits are worked out again on the next tick
+        let circuits: Vec<IVec3> = self.chunks[&coordinate]
+            .blocks_where(|b| b.is_circuit())
+            .iter()
+            .map(|b| Self::block_at(&glm::vec3(b.position.0, b.position.1, b.position.2)))
+            .collect();
+        for block in circuits.iter() {
+            self.circuits.touch(block);
+        }
+    }
+
+    // restores a previously unloaded chunk and its entities, false if it was never saved
+    fn load_chunk(&mut self, coordinate: ChunkCoordinate) -> bool {
+        let data = match self.store.load(&coordinate) {
+            Some(data) => data,
+            None => return false,
+        };
+        let mut chunk = Chunk::load(coordinate, &data);
+        chunk.update_visible();
+        light::light_chunk(&mut chunk);
+        self.entities.load_chunk(&data);
+        self.generated.push(coordinate);
+        self.insert_chunk(chunk);
+        true
+    }
+
+    fn unload_chunk(&mut self, coordinate: &ChunkCoordinate) {
+        if let Some(chunk) = self.chunks.remove(coordinate) {
+            let data = chunk.save() + &self.entities.unload_chunk(coordinate);
+            self.store.save(*coordinate, data);
+            self.circuits.unload_chunk(coordinate);
+        }
+        self.generated.retain(|c| c != coordinate);
+    }
+
+    // block the player's feet spawn in, searched for when the world is first played
+    pub fn spawn_point(&mut self) -> IVec3 {
+        if let Some(spawn) = self.spawn {
+            return spawn;
+        }
+        let spawn = self.store.load_metadata().and_then(|data| Self::read_spawn(&data)).unwrap_or_else(|| {
+            let generator = self.generator.lock().unwrap();
+            spawn::find_spawn(&*generator, spawn::SEARCH_RADIUS)
+                .unwrap_or_else(|| glm::vec3(0, generator.surface_height(0, 0) + 1, 0))
+        });
+        self.spawn = Some(spawn);
+        self.save_metadata();
+        spawn
+    }
+
+    fn read_spawn(metadata: &str) -> Option<IVec3> {
+        metadata.lines().find_map(|line| match line.split_whitespace().collect::<Vec<&str>>()[..] {
+            ["spawn", x, y, z] => Some(glm::vec3(x.parse().ok()?, y.parse().ok()?, z.parse().ok()?)),
+            _ => None,
+        })
+    }
+
+    fn save_metadata(&mut self) {
+        let mut data = String::new();
+        if let Some(spawn) = self.spawn {
+            data.push_str(&format!("spawn {} {} {}\n", spawn.x, spawn.y, spawn.z));
+        }
+        data.push_str(&self.clock.save());
+        self.store.save_metadata(data);
+    }
+
+    // writes every loaded chunk to the store, e.g. before exiting
+    pub fn save(&mut self) {
+        self.save_metadata();
+        for (coordinate, chunk) in self.chunks.iter() {
+            let data = chunk.save() + &self.entities.save_chunk(coordinate);
+            self.store.save(*coordinate, data);
+        }
+    }
+
+    pub fn update_chunks(&mut self, position: &Vec3, display: &Display, atlas: &Atlas) {
         let chunk_coord = Self::convert_to_chunk(&position);
 
-        for chunk in self.chunk_queue.lock().unwrap().drain(0..) {
-            self.chunks.insert(chunk.coordinates, chunk);
+        let generated: Vec<Chunk> = self.chunk_queue.lock().unwrap().drain(0..).collect();
+        for chunk in generated {
+            self.insert_chunk(chunk);
+        }
+
+        let keep = RENDER_DISTANCE + UNLOAD_MARGIN;
+        let far: Vec<ChunkCoordinate> = self.chunks.keys()
+            .filter(|c| (c[0] - chunk_coord[0]).abs() > keep || (c[1] - chunk_coord[1]).abs() > keep)
+            .copied()
+            .collect();
+        for coordinate in far {
+            self.unload_chunk(&coordinate);
         }
 
         for x in -RENDER_DISTANCE..=RENDER_DISTANCE {
@@ -46,6 +493,9 @@ impl World {
                 let current_chunk = Point2::new(chunk_coord[0] + x, chunk_coord[1] + z);
 
                 if !self.chunks.contains_key(&current_chunk) && !self.generated.contains(&current_chunk) {
+                    if self.load_chunk(current_chunk) {
+                        continue;
+                    }
                     println!("Generating chunk {}", current_chunk);
                     self.generated.push(current_chunk);
                     let queue = self.chunk_queue.clone();
@@ -53,32 +503,69 @@ impl World {
                     thread::spawn(move || {
                         let mut chunk = generator.lock().unwrap().generate(current_chunk);
                         chunk.update_visible();
+                        light::light_chunk(&mut chunk);
                         queue.lock().unwrap().push(chunk);
                     });
                     // self.chunks.insert(current_chunk, chunk);
-                } else if let Some(chunk) = self.chunks.get_mut(&current_chunk) {
-                    chunk.update_vbo(&display);
+                } else if self.chunks.get(&current_chunk).map_or(false, |c| c.needs_vbo()) {
+                    let sections = self.chunk_mesh(&current_chunk, atlas);
+                    self.chunks.get_mut(&current_chunk).unwrap().set_vbo(display, sections);
                 }
 
             }
         }
     }
 
-    pub fn intersect(&mut self, position: &Vec3, ray: &Vec3, action: &IntersectAction) {
-        let mut march = VoxelMarch::new(position, &ray);
-        for _ in 0..200 {
-            let block = march.next().unwrap().0;
-            let chunk = Self::convert_to_chunk(&glm::convert(block));
-            //println!("collide {}", chunk);
-            let chunk = self.chunks.get_mut(&chunk);
-            if let Some(chunk) = chunk {
-                if chunk.intersect(&mut march, &action) {
-                    break;
-                }
-            } else {
-                break;
+    pub fn set_block(&mut self, block: &IVec3, block_type: BlockType) -> bool {
+        let (chunk, local) = Self::locate(block);
+        match self.chunks.get_mut(&chunk) {
+            Some(chunk) => {
+                chunk.set_block(local, block_type);
+                self.changed(block);
+                true
             }
+            None => false,
+        }
+    }
+
+    pub fn remove_block(&mut self, block: &IVec3) -> Option<BlockType> {
+        let (chunk, local) = Self::locate(block);
+        let removed = self.chunks.get_mut(&chunk).and_then(|c| c.remove_block(&local));
+        if removed.is_some() {
+            self.changed(block);
         }
+        removed
+    }
+
+    // logs an edit and wakes up the block and any neighbours that react to changes
+    fn changed(&mut self, block: &IVec3) {
+        self.record_change(block);
+        self.invalidate_near(block);
+        light::update(self, block);
+        let neighbours = [(0, 0, 0), (1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+        for (x, y, z) in neighbours.iter() {
+            let neighbour = block + glm::vec3(*x, *y, *z);
+            if let Some(delay) = self.get_block(&neighbour).and_then(|b| self.block_ticks.delay(*b)) {
+                self.block_ticks.schedule(&neighbour, delay);
+            }
+        }
+        self.circuits.touch(block);
+    }
+
+    // first non-liquid block along the ray and the normal of the face it was entered through
+    pub fn raycast(&self, origin: &Vec3, direction: &Vec3, steps: usize) -> Option<(IVec3, IVec3)> {
+        // blocks are centred on integer coordinates while the march walks cells starting at them
+        let mut march = VoxelMarch::new(&origin.add_scalar(0.5), &direction);
+        for _ in 0..steps {
+            let (block, normal) = march.next().unwrap();
+            if !self.is_loaded(&block) {
+                return None;
+            }
+            if self.get_block(&block).map_or(false, |b| !b.is_liquid()) {
+                return Some((block, normal));
+            }
+        }
+        None
     }
 
     pub fn rendered_chunks(&self, position: &Vec3) -> Vec<&Chunk> {
@@ -96,4 +583,53 @@ impl World {
 
         output
     }
+
+    // keeps the translucent faces of the chunks being drawn sorted from the camera
+    pub fn sort_translucent(&mut self, position: &Vec3) {
+        let chunk_coord = Self::convert_to_chunk(position);
+        let block = Self::block_at(position);
+        for chunk in self.chunks.values_mut() {
+            let (dx, dz) = (chunk.coordinates[0] - chunk_coord[0], chunk.coordinates[1] - chunk_coord[1]);
+            if dx.abs() <= RENDER_DISTANCE && dz.abs() <= RENDER_DISTANCE {
+                chunk.sort_translucent(position, block);
+            }
+        }
+    }
+
+    // Sections (chunk x, section, chunk z) that could be seen from the position through the
+    // open space between them, within the render distance. A camera above the top section or
+    // below the bottom one starts from the nearest. None while the camera's own chunk hasn't
+    // loaded, since then nothing can be ruled out.
+    pub fn visible_sections(&self, position: &Vec3, in_view: impl Fn(&IVec3) -> bool) -> Option<HashSet<IVec3>> {
+        let chunk_coord = Self::convert_to_chunk(position);
+        let section = visibility::section_of(Self::block_at(position).y).max(0).min(visibility::TOP_SECTION);
+        let start = glm::vec3(chunk_coord[0], section, chunk_coord[1]);
+        let connectivity = |s: &IVec3| {
+            let near = (s.x - chunk_coord[0]).abs() <= RENDER_DISTANCE && (s.z - chunk_coord[1]).abs() <= RENDER_DISTANCE;
+            if !near || s.y < 0 || s.y > visibility::TOP_SECTION {
+                return None;
+            }
+            self.chunks.get(&Point2::new(s.x, s.z)).map(|chunk| chunk.connectivity(s.y))
+        };
+        connectivity(&start)?;
+        Some(visibility::visible_sections(start, connectivity, in_view))
+    }
+}
+
+#[cfg(test)]
+impl World {
+    // Test fixture: PlanarGenerator's floor at world height 16, so its top face is at 16.5 and
+    // things stand on it at 17, generated out to radius chunks around the origin. Its surface
+    // cycles dirt, sand, stone, grass along x + z; supported lays dirt under it so the sand
+    // doesn't fall.
+    pub fn flat(radius: i32, supported: bool) -> World {
+        let generator = if supported { PlanarGenerator::supported() } else { PlanarGenerator::new() };
+        let mut world = World::with_generator(generator);
+        for x in -radius..=radius {
+            for z in -radius..=radius {
+                world.generate_chunk(Point2::new(x, z));
+            }
+        }
+        world
+    }
 }
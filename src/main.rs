@@ -1,5 +1,6 @@
 use glium::glutin;
 use std::env;
+use std::path::Path;
 extern crate nalgebra_glm as glm;
 
 use std::time::{Duration, Instant};
@@ -9,12 +10,78 @@ mod engine;
 mod primitives;
 mod camera;
 
+use engine::atlas::Atlas;
+use engine::crafting::Recipes;
+use engine::generator::{EdgeMode, HeightmapGenerator};
+use engine::inventory::GameMode;
+use engine::mob::Mobs;
+use engine::seed::Seed;
+use engine::storage::ChunkStore;
+use engine::world::{World, SKY_HEIGHT};
+
+const DEFAULT_TEXTURES: &str = "data/textures";
+const RECIPES: &str = "data/recipes.txt";
+const MOB_DEFINITIONS: &str = "data/mobs.txt";
+
+// usage: boxel [seed] [--survival] [--world <dir>] [--textures <dir>]
+//              [--heightmap <png> [--materials <png>] [--clamp] [--height-range <min> <max>]]
+// the height range is the world heights black and white map to, 2 to 32 by default
+// while it runs, commands such as "time set noon" or "time freeze" can be typed into the terminal
 fn main() {
-    let mut seed: u32 = rand::random(); 
-    if env::args().len() > 1 {
-        seed = env::args().nth(1).unwrap().parse().unwrap();
+    let mut seed = Seed::random();
+    let mut heightmap: Option<String> = None;
+    let mut materials: Option<String> = None;
+    let mut edges = EdgeMode::Tile;
+    let mut height_range: Option<(i32, i32)> = None;
+    let mut mode = GameMode::Creative;
+    let mut save_dir: Option<String> = None;
+    let mut textures: Option<String> = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--heightmap" => heightmap = args.next(),
+            "--materials" => materials = args.next(),
+            "--clamp" => edges = EdgeMode::Clamp,
+            "--height-range" => {
+                let mut height = || args.next().and_then(|h| h.parse().ok()).expect("--height-range takes two whole numbers");
+                height_range = Some((height(), height()));
+            }
+            "--survival" => mode = GameMode::Survival,
+            "--world" => save_dir = args.next(),
+            "--textures" => textures = args.next(),
+            _ => seed = arg.parse().unwrap(), // infallible, any string is a seed
+        }
+    }
+
+    let mut world = match heightmap {
+        Some(path) => {
+            let mut generator = HeightmapGenerator::open(path, materials, edges).expect("to load heightmap");
+            if let Some((min, max)) = height_range {
+                // the surface is searched for from SKY_HEIGHT down
+                assert!(0 <= min && min <= max && max < SKY_HEIGHT, "--height-range needs 0 <= min <= max < {}", SKY_HEIGHT);
+                generator.set_height_range(min, max);
+            }
+            World::seeded(generator, &seed)
+        }
+        None => World::new(&seed),
+    };
+    // a heightmap world still takes its block ticks and mob spawning from the seed
+    println!("Seed: {}", seed);
+
+    if let Some(dir) = save_dir {
+        world.set_store(ChunkStore::open(dir.into()).expect("to open world directory"));
     }
 
+    // the bundled pack unless another is given, flat colours if neither is there
+    let atlas = match textures {
+        Some(dir) => Atlas::load(Path::new(&dir)).expect("to load texture pack"),
+        None if Path::new(DEFAULT_TEXTURES).is_dir() => Atlas::load(Path::new(DEFAULT_TEXTURES)).expect("to load texture pack"),
+        None => Atlas::blank(),
+    };
+    let recipes = Recipes::load(Path::new(RECIPES)).expect("to load recipes");
+    let mobs = Mobs::load(Path::new(MOB_DEFINITIONS)).expect("to load mob definitions");
+
     let event_loop = glutin::event_loop::EventLoop::new();
 
     let window = glutin::window::WindowBuilder::new()
@@ -28,23 +95,11 @@ fn main() {
     let display = glium::Display::new(window, context, &event_loop).unwrap();
     let _scale_factor = display.gl_window().window().scale_factor();
 
-    let mut engine = engine::Engine::new(display, seed);
+    let mut engine = engine::Engine::new(display, world, &seed, mode, atlas, recipes, mobs);
 
     let mut last_frame = Instant::now();
 
     event_loop.run(move |ev, _, control_flow| {
-
-        let now = Instant::now();
-        let delta_time = now - last_frame;
-        let next_frame_time = now + Duration::from_nanos(16_666_667);
-        last_frame = now;
-
-        let fps = 1.0 / delta_time.as_secs_f32();
-        if fps < 50.0 {
-            println!("FPS: {}/s", 1.0 / delta_time.as_secs_f32());
-        }
-
-        *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
         use glium::glutin::{event, event_loop};
         match ev {
             /*
@@ -54,30 +109,53 @@ fn main() {
             */
             event::Event::WindowEvent { event, .. } => match event {
                 event::WindowEvent::CloseRequested => {
+                    engine.save();
                     *control_flow = event_loop::ControlFlow::Exit;
-                    return;
                 }
                 event::WindowEvent::KeyboardInput { input, .. } => {
                     let pressed = input.state == event::ElementState::Pressed;
                     if let Some(key) = input.virtual_keycode {
-                        engine.process_keyboard(pressed, key, delta_time);
+                        engine.process_keyboard(pressed, key);
                     }
                 }
                 event::WindowEvent::MouseInput { state, button, .. } => {
-                    if state == event::ElementState::Pressed {
-                        engine.process_click(button);
+                    engine.process_click(button, state == event::ElementState::Pressed);
+                }
+                event::WindowEvent::MouseWheel { delta, .. } => {
+                    let lines = match delta {
+                        event::MouseScrollDelta::LineDelta(_, y) => y,
+                        event::MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                    };
+                    if lines != 0.0 {
+                        engine.process_scroll(lines);
                     }
                 }
-                _ => return,
+                _ => (),
             },
-            event::Event::DeviceEvent { event, .. } => match event {
-                event::DeviceEvent::MouseMotion { delta } => {
-                    engine.process_cursor(delta, delta_time);
+            event::Event::DeviceEvent { event, .. } => {
+                if let event::DeviceEvent::MouseMotion { delta } = event {
+                    engine.process_cursor(delta);
                 }
-                _ => return,
-            },
+            }
+            // all input for this iteration has been handled: simulate, then draw one frame
+            event::Event::MainEventsCleared => {
+                let now = Instant::now();
+                let delta_time = now - last_frame;
+                last_frame = now;
+
+                let fps = 1.0 / delta_time.as_secs_f32();
+                if fps < 50.0 {
+                    println!("FPS: {}/s", fps);
+                }
+
+                engine.update(delta_time);
+                engine.render(fps);
+
+                if *control_flow != event_loop::ControlFlow::Exit {
+                    *control_flow = event_loop::ControlFlow::WaitUntil(now + Duration::from_nanos(16_666_667));
+                }
+            }
             _ => (),
         }
-        engine.render(1.0 / delta_time.as_secs_f32());
     });
 }
@@ -4,9 +4,12 @@ use glium::implement_vertex;
 pub struct InstanceAttr {
     pub world_position: (f32, f32, f32),
     pub color: (f32, f32, f32, f32),
+    pub scale: f32,
+    pub light_positive: (f32, f32, f32), // brightness of the faces facing +x, +y and +z
+    pub light_negative: (f32, f32, f32),
 }
 
-implement_vertex!(InstanceAttr, world_position, color);
+implement_vertex!(InstanceAttr, world_position, color, scale, light_positive, light_negative);
 
 #[derive(Copy, Clone, Constructor)]
 pub struct CrosshairVertex {
@@ -32,11 +35,15 @@ pub struct ColorVertex {
 
 implement_vertex!(ColorVertex, position, normal, color);
 
+// a corner of a block face in a chunk mesh
 #[derive(Copy, Clone, Constructor)]
 pub struct TextureVertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
-    pub texture: [f32; 2],
+    pub texture: [f32; 2], // in the atlas
+    pub color: [f32; 4], // tints the texture, white for a textured face
+    pub light: [f32; 2], // sky and block brightness in front of the face, sky light dims at night
+    pub occlusion: f32, // 0 for a corner boxed in by its neighbours, 1 for an open one
 }
 
-implement_vertex!(TextureVertex, position, normal, texture);
+implement_vertex!(TextureVertex, position, normal, texture, color, light, occlusion);
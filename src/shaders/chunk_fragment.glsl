@@ -0,0 +1,29 @@
+#version 140
+uniform vec3 camera_position;
+uniform vec3 fog_color;
+uniform float fog_density; // 0 when the camera isn't inside a liquid
+uniform vec3 light_direction; // towards the sun or the moon
+uniform vec3 light_color; // fades to nothing as they near the horizon
+uniform sampler2D atlas; // every block texture, faces without one sample a white tile
+in vec3 v_position;
+in vec3 v_normal;
+in vec2 v_texture;
+in vec4 v_color;
+in float v_occlusion;
+out vec4 f_color;
+// brightness left in a corner boxed in on both sides
+const float OCCLUDED = 0.45;
+void main() {
+  vec4 texel = texture(atlas, v_texture);
+  // fully clear pixels cut holes, and mustn't hide what's behind them in the depth buffer
+  if (texel.a == 0.0) {
+    discard;
+  }
+  // only shapes the faces, how lit a face is comes in with its colour
+  float lum = max(dot(normalize(v_normal), normalize(light_direction)), 0.0);
+  float occlusion = mix(OCCLUDED, 1.0, v_occlusion);
+  vec3 color = occlusion * (0.3 + 0.7 * lum * light_color) * vec3(v_color) * vec3(texel);
+  float fog = 1.0 - exp(-fog_density * distance(v_position, camera_position));
+  color = mix(color, fog_color, fog);
+  f_color = vec4(color, v_color[3] * texel.a);
+}
\ No newline at end of file
@@ -0,0 +1,23 @@
+#version 140
+uniform mat4 persp_matrix;
+uniform mat4 view_matrix;
+uniform float ambient; // how much sky light is left at this time of day
+in vec3 position; //world space, chunk meshes aren't instanced
+in vec3 normal;
+in vec2 texture; //in the atlas
+in vec4 color; //tints the texture
+in vec2 light; //sky and block brightness in front of the face
+in float occlusion; //0 for a boxed in corner, 1 for an open one
+out vec3 v_position;
+out vec3 v_normal;
+out vec2 v_texture;
+out vec4 v_color;
+out float v_occlusion;
+void main() {
+    v_position = position;
+    v_normal = normal;
+    v_texture = texture;
+    v_color = vec4(vec3(color) * max(light.x * ambient, light.y), color[3]);
+    v_occlusion = occlusion;
+    gl_Position = persp_matrix * view_matrix * vec4(v_position, 1.0);
+}
\ No newline at end of file
@@ -1,10 +1,18 @@
 #version 140
+uniform vec3 camera_position;
+uniform vec3 fog_color;
+uniform float fog_density; // 0 when the camera isn't inside a liquid
+uniform vec3 light_direction; // towards the sun or the moon
+uniform vec3 light_color; // fades to nothing as they near the horizon
+in vec3 v_position;
 in vec3 v_normal;
 in vec4 v_color;
 out vec4 f_color;
-const vec3 LIGHT = vec3(-0.2, 0.8, 0.1);
 void main() {
-  float lum = max(dot(normalize(v_normal), normalize(LIGHT)), 0.0);
-  vec3 color = (0.3 + 0.7 * lum) * vec3(v_color);
+  // only shapes the faces, how lit a face is comes in with its colour
+  float lum = max(dot(normalize(v_normal), normalize(light_direction)), 0.0);
+  vec3 color = (0.3 + 0.7 * lum * light_color) * vec3(v_color);
+  float fog = 1.0 - exp(-fog_density * distance(v_position, camera_position));
+  color = mix(color, fog_color, fog);
   f_color = vec4(color, v_color[3]);
 }
\ No newline at end of file
@@ -0,0 +1,20 @@
+#version 140
+uniform vec3 horizon_color;
+uniform vec3 zenith_color;
+uniform vec3 sun_direction;
+in vec3 v_direction;
+out vec4 f_color;
+// cosine of the angle the sun and moon discs cover
+const float DISC = 0.9993;
+const vec3 SUN = vec3(1.0, 0.95, 0.8);
+const vec3 MOON = vec3(0.8, 0.82, 0.9);
+void main() {
+  vec3 direction = normalize(v_direction);
+  vec3 color = mix(horizon_color, zenith_color, sqrt(max(direction.y, 0.0)));
+  if (dot(direction, normalize(sun_direction)) > DISC) {
+    color = SUN;
+  } else if (dot(direction, -normalize(sun_direction)) > DISC) {
+    color = MOON;
+  }
+  f_color = vec4(color, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+#version 140
+uniform mat4 inverse_view_projection;
+in vec2 screen_position;
+out vec3 v_direction;
+void main() {
+    // the point on the far plane behind this corner, relative to the camera
+    vec4 far = inverse_view_projection * vec4(screen_position, 1.0, 1.0);
+    vec4 near = inverse_view_projection * vec4(screen_position, -1.0, 1.0);
+    v_direction = far.xyz / far.w - near.xyz / near.w;
+    gl_Position = vec4(screen_position, 1.0, 1.0);
+}
\ No newline at end of file
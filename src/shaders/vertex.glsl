@@ -4,13 +4,17 @@ uniform mat4 view_matrix;
 in vec3 position;
 in vec4 color; //from instance VBO
 in vec3 world_position; //From instance VBO
+in float scale; //From instance VBO
+in vec3 light_positive; //From instance VBO, brightness of the +x, +y and +z faces
+in vec3 light_negative; //From instance VBO
 in vec3 normal;
 out vec3 v_position;
 out vec3 v_normal;
 out vec4 v_color;
 void main() {
-    v_position = position + world_position;
+    v_position = position * scale + world_position;
     v_normal = normal;
-    v_color = color;
+    float light = dot(max(normal, 0.0), light_positive) + dot(max(-normal, 0.0), light_negative);
+    v_color = vec4(vec3(color) * light, color[3]);
     gl_Position = persp_matrix * view_matrix * vec4(v_position, 1.0);
 }
\ No newline at end of file